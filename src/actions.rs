@@ -0,0 +1,264 @@
+use glutin::event::{MouseButton, VirtualKeyCode};
+use gilrs::Button;
+
+use crate::game::MAX_PLAYERS;
+use crate::kinput::*;
+
+// What the game wants to know about (move, fire, pause...) rather than which keys
+// are down. Every player has their own map from actions to whatever keys, mouse
+// buttons and pad buttons they like, pad buttons being on that players pad.
+// FrameInputs::action_held and action_rising answer for a map.
+//
+// They get saved with the rest of the settings, one line per player and action:
+//
+// bind p<n> <action> <binding>...
+//
+// where a binding is a key by name like in headless scripts, mouse:left|right|middle
+// or pad:<button> (South, East, North, West, DPadUp, Start, RightTrigger2...).
+// Nothing after the action leaves it unbound.
+
+pub const MAX_BINDS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Switch,
+    Restart,
+    Pause,
+}
+
+pub const ACTIONS: [Action; 8] = [
+    Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight,
+    Action::Fire, Action::Switch, Action::Restart, Action::Pause,
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "up",
+            Action::MoveDown => "down",
+            Action::MoveLeft => "left",
+            Action::MoveRight => "right",
+            Action::Fire => "fire",
+            Action::Switch => "switch",
+            Action::Restart => "restart",
+            Action::Pause => "pause",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    Pad(Button),
+}
+
+pub const PAD_BUTTONS: [Button; 17] = [
+    Button::South, Button::East, Button::North, Button::West,
+    Button::LeftTrigger, Button::LeftTrigger2, Button::RightTrigger, Button::RightTrigger2,
+    Button::Select, Button::Start, Button::Mode, Button::LeftThumb, Button::RightThumb,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+];
+
+impl Binding {
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(k) => key_name(*k),
+            Binding::Mouse(MouseButton::Left) => "mouse:left".to_owned(),
+            Binding::Mouse(MouseButton::Right) => "mouse:right".to_owned(),
+            Binding::Mouse(_) => "mouse:middle".to_owned(),
+            Binding::Pad(b) => format!("pad:{:?}", b),
+        }
+    }
+
+    // shorter, for showing on screen
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(k) => key_name(*k),
+            Binding::Mouse(MouseButton::Left) => "lmb".to_owned(),
+            Binding::Mouse(MouseButton::Right) => "rmb".to_owned(),
+            Binding::Mouse(_) => "mmb".to_owned(),
+            Binding::Pad(b) => format!("pad {}", match b {
+                Button::South => "a",
+                Button::East => "b",
+                Button::North => "y",
+                Button::West => "x",
+                Button::LeftTrigger => "lb",
+                Button::LeftTrigger2 => "lt",
+                Button::RightTrigger => "rb",
+                Button::RightTrigger2 => "rt",
+                Button::LeftThumb => "ls",
+                Button::RightThumb => "rs",
+                Button::DPadUp => "up",
+                Button::DPadDown => "down",
+                Button::DPadLeft => "left",
+                Button::DPadRight => "right",
+                Button::Start => "start",
+                Button::Select => "select",
+                _ => "mode",
+            }),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Binding> {
+        if let Some(m) = name.strip_prefix("mouse:") {
+            match m {
+                "left" => Some(Binding::Mouse(MouseButton::Left)),
+                "right" => Some(Binding::Mouse(MouseButton::Right)),
+                "middle" => Some(Binding::Mouse(MouseButton::Middle)),
+                _ => None,
+            }
+        } else if let Some(b) = name.strip_prefix("pad:") {
+            PAD_BUTTONS.iter().copied().find(|button| format!("{:?}", button).eq_ignore_ascii_case(b)).map(Binding::Pad)
+        } else {
+            key_from_name(name).map(Binding::Key)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActionMap {
+    // which of FrameInputs::pads pad bindings get read from
+    pub pad: usize,
+    binds: [[Option<Binding>; MAX_BINDS]; ACTIONS.len()],
+}
+
+impl ActionMap {
+    pub fn new(pad: usize) -> ActionMap {
+        ActionMap { pad, binds: [[None; MAX_BINDS]; ACTIONS.len()] }
+    }
+
+    pub fn bindings(&self, action: Action) -> impl Iterator<Item = Binding> + '_ {
+        self.binds[action as usize].iter().flatten().copied()
+    }
+
+    pub fn contains(&self, action: Action, binding: Binding) -> bool {
+        self.bindings(action).any(|b| b == binding)
+    }
+
+    // goes in the first free slot, or over the last one if theyre all used
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        if self.contains(action, binding) {
+            return;
+        }
+        let slots = &mut self.binds[action as usize];
+        let i = slots.iter().position(|b| b.is_none()).unwrap_or(MAX_BINDS - 1);
+        slots[i] = Some(binding);
+    }
+
+    pub fn unbind(&mut self, action: Action, binding: Binding) {
+        let slots = &mut self.binds[action as usize];
+        slots.iter_mut().filter(|b| **b == Some(binding)).for_each(|b| *b = None);
+        // keep them packed at the front so the first one is the one to show
+        slots.sort_by_key(|b| b.is_none());
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.binds[action as usize] = [None; MAX_BINDS];
+    }
+
+    // for telling people what to press
+    pub fn label(&self, action: Action) -> String {
+        self.bindings(action).next().map(|b| b.label()).unwrap_or_else(|| "-".to_owned())
+    }
+}
+
+// everyones the same on a pad, on the keyboard theyre spread out so four can fit
+pub fn default_bindings() -> [ActionMap; MAX_PLAYERS] {
+    const KEYS: [[VirtualKeyCode; 6]; MAX_PLAYERS] = [
+        [VirtualKeyCode::W, VirtualKeyCode::S, VirtualKeyCode::A, VirtualKeyCode::D, VirtualKeyCode::Space, VirtualKeyCode::Q],
+        [VirtualKeyCode::Up, VirtualKeyCode::Down, VirtualKeyCode::Left, VirtualKeyCode::Right, VirtualKeyCode::RControl, VirtualKeyCode::RShift],
+        [VirtualKeyCode::I, VirtualKeyCode::K, VirtualKeyCode::J, VirtualKeyCode::L, VirtualKeyCode::H, VirtualKeyCode::U],
+        [VirtualKeyCode::Numpad8, VirtualKeyCode::Numpad5, VirtualKeyCode::Numpad4, VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad0, VirtualKeyCode::NumpadAdd],
+    ];
+    const PAD: [&[Button]; 6] = [
+        &[Button::DPadUp], &[Button::DPadDown], &[Button::DPadLeft], &[Button::DPadRight],
+        &[Button::South, Button::RightTrigger2], &[Button::West, Button::RightTrigger],
+    ];
+    let mut maps = [ActionMap::new(0); MAX_PLAYERS];
+    for (i, map) in maps.iter_mut().enumerate() {
+        map.pad = i;
+        for (j, action) in ACTIONS[..6].iter().enumerate() {
+            map.bind(*action, Binding::Key(KEYS[i][j]));
+            for b in PAD[j] {
+                map.bind(*action, Binding::Pad(*b));
+            }
+        }
+        map.bind(Action::Pause, Binding::Pad(Button::Start));
+    }
+    maps[0].bind(Action::Restart, Binding::Key(VirtualKeyCode::R));
+    maps[0].bind(Action::Pause, Binding::Key(VirtualKeyCode::Escape));
+    maps
+}
+
+// keys the root scene looks at no matter whats bound, and what for
+pub const RESERVED_KEYS: [(VirtualKeyCode, &str); 4] = [
+    (VirtualKeyCode::Tab, "high scores"),
+    (VirtualKeyCode::F1, "settings"),
+    (VirtualKeyCode::F2, "demos"),
+    (VirtualKeyCode::Back, "unbinding"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conflict {
+    // someone has it, can be taken off them
+    Action(usize, Action),
+    // cant be had at all
+    Reserved(&'static str),
+}
+
+// Whoever else already has this. Everyones on the same keyboard and mouse but pads
+// are only shared with yourself.
+pub fn conflicts(maps: &[ActionMap; MAX_PLAYERS], player: usize, action: Action, binding: Binding) -> Vec<Conflict> {
+    let mut found = Vec::new();
+    if let Some((_, what)) = RESERVED_KEYS.iter().find(|(k, _)| binding == Binding::Key(*k)) {
+        found.push(Conflict::Reserved(what));
+    }
+    for (p, map) in maps.iter().enumerate() {
+        if matches!(binding, Binding::Pad(_)) && p != player {
+            continue;
+        }
+        for a in ACTIONS {
+            if (p, a) != (player, action) && map.contains(a, binding) {
+                found.push(Conflict::Action(p, a));
+            }
+        }
+    }
+    found
+}
+
+#[test]
+fn test_actions() {
+    let mut maps = default_bindings();
+    let space = Binding::Key(VirtualKeyCode::Space);
+    assert!(maps[0].contains(Action::Fire, space));
+    assert_eq!(maps[0].label(Action::MoveUp), "W");
+    assert_eq!(Binding::from_name("pad:south"), Some(Binding::Pad(Button::South)));
+    for b in [space, Binding::Mouse(MouseButton::Right), Binding::Pad(Button::DPadLeft)] {
+        assert_eq!(Binding::from_name(&b.name()), Some(b));
+    }
+
+    // space is p1 fire, p2 cant have it without taking it off them. pads only clash with yourself
+    assert_eq!(conflicts(&maps, 1, Action::Fire, space), vec![Conflict::Action(0, Action::Fire)]);
+    assert_eq!(conflicts(&maps, 1, Action::Fire, Binding::Pad(Button::West)), vec![Conflict::Action(1, Action::Switch)]);
+    // tab and f1 and so on belong to the root scene
+    assert_eq!(conflicts(&maps, 0, Action::Fire, Binding::Key(VirtualKeyCode::Tab)), vec![Conflict::Reserved("high scores")]);
+
+    maps[0].unbind(Action::Fire, space);
+    assert_eq!(maps[0].label(Action::Fire), "pad a");
+    for _ in 0..MAX_BINDS + 1 {
+        maps[0].bind(Action::Fire, space);
+    }
+    assert_eq!(maps[0].bindings(Action::Fire).count(), 3);
+    maps[0].clear(Action::Fire);
+    assert_eq!(maps[0].label(Action::Fire), "-");
+}
@@ -11,7 +11,7 @@ use glutin::{event_loop::*, event::{Event, WindowEvent}};
 
 pub struct Application {
     video: Video,
-    stream: Stream,
+    stream: Option<Stream>,
     channel: Producer<u32>,
 
     root_scene: RootScene,
@@ -19,7 +19,7 @@ pub struct Application {
 }
 
 impl Application {
-    pub fn new(event_loop: &EventLoop<()>) -> Application {
+    pub fn new(event_loop: &EventLoop<()>, root_scene: RootScene) -> Application {
         let xres = 1600;
         let yres = 1600;
     
@@ -30,15 +30,17 @@ impl Application {
         
         let mut app = Application {
             video,
-            root_scene: RootScene::new(),
+            root_scene,
             event_handler: EventAggregator::new(xres as f32, yres as f32),
-            stream: stream_setup_for(sample_next, cons).expect("no can make stream"),
+            stream: stream_setup_for(sample_next, cons).map_err(|e| println!("no sound: {}", e)).ok(),
 
             channel: prod,
 
         };
 
-        app.stream.play().expect("no can play stream");
+        if let Some(stream) = app.stream.as_ref() {
+            stream.play().expect("no can play stream");
+        }
         app
     }
 
@@ -63,6 +65,9 @@ impl Application {
                     for e in new_outputs.audio_events {
                         self.channel.push(e).ok();
                     }
+                    if self.root_scene.quit() {
+                        self.exit();
+                    }
                 }
             }
         }
@@ -0,0 +1,368 @@
+use crate::kmath::*;
+use crate::renderers::simple_renderer::*;
+use crate::pattern::*;
+use crate::settings::Palette;
+
+// Enemy types, read out of enemies.txt (see the top of that file for the format)
+
+pub const ARCHETYPE_FILE: &str = "enemies.txt";
+const BUILTIN: &str = include_str!("../enemies.txt");
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Movement {
+    Straight,
+    Wander { amp: f32 },
+    Sine { amp: f32, freq: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Random,
+    Y(f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spawn {
+    pub after: f32,
+    pub interval: f32,
+    pub duty_cycle: f32,
+    pub len: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShapeKind {
+    Rect { c: Vec2, w: f32, h: f32 },
+    Circle { c: Vec2, r: f32 },
+    Tri { a: Vec2, b: Vec2, c: Vec2 },
+    VPill { c: Vec2, w: f32, h: f32 },
+    Orbit { n: u32, dist: f32, r: f32, speed: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub colour: Option<Vec4>,
+}
+
+// only these take laser damage if an enemy has any
+#[derive(Clone, Debug, PartialEq)]
+pub struct WeakPoint {
+    pub c: Vec2,
+    pub w: f32,
+    pub h: f32,
+}
+
+// what it switches to once its health drops to below of the max
+#[derive(Clone, Debug, PartialEq)]
+pub struct Phase {
+    pub below: f32,
+    pub movement: Movement,
+    pub fire: Option<Pattern>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Archetype {
+    pub name: String,
+    pub w: f32,
+    pub h: f32,
+    pub speed: f32,
+    pub colour: Vec4,
+    pub movement: Movement,
+    pub fire: Option<Pattern>,
+    pub spawn: Option<Spawn>,
+    pub entry: Entry,
+    pub shapes: Vec<Shape>,
+    // hitbox is a circle w across instead of a rect
+    pub round: bool,
+    pub health: f32,
+    // what killing one is worth
+    pub points: i32,
+    pub weak_points: Vec<WeakPoint>,
+    pub phases: Vec<Phase>,
+}
+
+impl Archetype {
+    fn new(name: &str) -> Archetype {
+        Archetype {
+            name: name.to_owned(),
+            w: 0.05,
+            h: 0.05,
+            speed: 0.1,
+            colour: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            movement: Movement::Straight,
+            fire: None,
+            spawn: Some(Spawn { after: 0.0, interval: 1.0, duty_cycle: 1.0, len: 1.0 }),
+            entry: Entry::Random,
+            shapes: Vec::new(),
+            round: false,
+            health: 0.0,
+            points: 10,
+            weak_points: Vec::new(),
+            phases: Vec::new(),
+        }
+    }
+
+    // 0 is how it starts out, after that its phases[phase - 1]
+    pub fn phase(&self, health: f32) -> usize {
+        if self.health <= 0.0 {
+            return 0;
+        }
+        let frac = health / self.health;
+        self.phases.iter().rposition(|p| frac <= p.below).map(|i| i + 1).unwrap_or(0)
+    }
+
+    pub fn movement(&self, phase: usize) -> &Movement {
+        if phase == 0 { &self.movement } else { &self.phases[phase - 1].movement }
+    }
+
+    pub fn fire(&self, phase: usize) -> Option<&Pattern> {
+        if phase == 0 { self.fire.as_ref() } else { self.phases[phase - 1].fire.as_ref() }
+    }
+
+    // does the laser hurt it, only the weak points count if there are any
+    pub fn hit_by(&self, p: Vec2, laser: Rect) -> bool {
+        if !self.weak_points.is_empty() {
+            return self.weak_points.iter().any(|wp| laser.overlaps((p + wp.c).rect_centered(wp.w, wp.h)).is_some());
+        }
+        if self.round {
+            return laser.snap(p).dist(p) <= self.w/2.0;
+        }
+        laser.overlaps(p.rect_centered(self.w, self.h)).is_some()
+    }
+
+    // how far along the ray it stops and whether that hurts it. weak points are
+    // exposed so the ray gets to them, if it misses them the rest of it blocks the ray
+    pub fn ray_hit(&self, p: Vec2, origin: Vec2, dir: Vec2) -> Option<(f32, bool)> {
+        let weak = self.weak_points.iter()
+            .filter_map(|wp| ray_rect(origin, dir, (p + wp.c).rect_centered(wp.w, wp.h)))
+            .fold(None, |acc: Option<f32>, d| Some(acc.map_or(d, |a| a.min(d))));
+        if let Some(d) = weak {
+            return Some((d, true));
+        }
+        let body = if self.round {
+            ray_circle(origin, dir, p, self.w/2.0)
+        } else {
+            ray_rect(origin, dir, p.rect_centered(self.w, self.h))
+        };
+        body.map(|d| (d, self.weak_points.is_empty()))
+    }
+
+    // vertical speed for one that was born at dob
+    pub fn vy(&self, phase: usize, t: f32, dob: f32, seed: u32) -> f32 {
+        match *self.movement(phase) {
+            Movement::Straight => 0.0,
+            Movement::Wander { amp } => amp * (noise1d(t, seed) - 0.5) * 2.0,
+            Movement::Sine { amp, freq } => amp * (freq * (t - dob)).cos(),
+        }
+    }
+
+    // flash draws it all white for when its been hit
+    pub fn draw(&self, canvas: &mut SimpleCanvas, p: Vec2, t: f32, flash: bool, palette: Palette) {
+        let white = Vec4::new(1.0, 1.0, 1.0, 1.0);
+        if self.shapes.is_empty() {
+            canvas.put_rect(p.rect_centered(self.w, self.h), 1.5, if flash { white } else { palette.enemy(self.colour) });
+        }
+        for (i, shape) in self.shapes.iter().enumerate() {
+            let c = if flash { white } else { palette.enemy(shape.colour.unwrap_or(self.colour)) };
+            let depth = 1.5 + i as f32 * 0.001;
+            match shape.kind {
+                ShapeKind::Rect { c: o, w, h } => canvas.put_rect((p + o).rect_centered(w, h), depth, c),
+                ShapeKind::Circle { c: o, r } => canvas.put_circle(p + o, r, depth, c),
+                ShapeKind::Tri { a, b, c: c3 } => canvas.put_triangle(p + a, p + b, p + c3, depth, c),
+                ShapeKind::VPill { c: o, w, h } => canvas.put_vpill(p + o, w, h, depth, c),
+                ShapeKind::Orbit { n, dist, r, speed } => {
+                    let phase = speed * t;
+                    for k in 0..n {
+                        let theta = phase + k as f32 * TAU / n as f32;
+                        canvas.put_circle(p + dist * v2(theta.sin(), theta.cos()), r, depth, c);
+                    }
+                },
+            }
+        }
+    }
+}
+
+pub fn parse_archetypes(s: &str) -> Result<Vec<Archetype>, anyhow::Error> {
+    let mut archetypes: Vec<Archetype> = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        parse_line(&words, &mut archetypes).map_err(|e| anyhow::Error::msg(format!("line {}: {}", i + 1, e)))?;
+    }
+    if archetypes.is_empty() {
+        return Err(anyhow::Error::msg("no enemies in there"));
+    }
+    Ok(archetypes)
+}
+
+fn parse_line(words: &[&str], archetypes: &mut Vec<Archetype>) -> Result<(), anyhow::Error> {
+    if words[0] == "enemy" {
+        let name = words.get(1).ok_or_else(|| anyhow::Error::msg("enemy needs a name"))?;
+        archetypes.push(Archetype::new(name));
+        return Ok(());
+    }
+    let a = archetypes.last_mut().ok_or_else(|| anyhow::Error::msg(format!("'{}' before any enemy", words[0])))?;
+    let args = &words[1..];
+    match words[0] {
+        "size" => {
+            let v = floats(args, 2)?;
+            a.w = v[0];
+            a.h = v[1];
+        },
+        "speed" => a.speed = floats(args, 1)?[0],
+        "colour" => a.colour = colour(args)?,
+        "move" => {
+            let movement = match args.first() {
+                Some(&"straight") => Movement::Straight,
+                Some(&"wander") => Movement::Wander { amp: floats(&args[1..], 1)?[0] },
+                Some(&"sine") => {
+                    let v = floats(&args[1..], 2)?;
+                    Movement::Sine { amp: v[0], freq: v[1] }
+                },
+                _ => return Err(anyhow::Error::msg("move is straight, wander or sine")),
+            };
+            match a.phases.last_mut() {
+                Some(phase) => phase.movement = movement,
+                None => a.movement = movement,
+            }
+        },
+        "fire" => {
+            let fire = Pattern::parse(args)?;
+            match a.phases.last_mut() {
+                Some(phase) => phase.fire = fire,
+                None => a.fire = fire,
+            }
+        },
+        "round" => a.round = true,
+        "health" => a.health = floats(args, 1)?[0],
+        "points" => a.points = floats(args, 1)?[0] as i32,
+        "weakpoint" => {
+            let v = floats(args, 4)?;
+            a.weak_points.push(WeakPoint { c: v2(v[0], v[1]), w: v[2], h: v[3] });
+        },
+        "phase" => {
+            let below = floats(args, 1)?[0];
+            let (movement, fire) = (a.movement(a.phases.len()).clone(), a.fire(a.phases.len()).cloned());
+            if a.phases.last().map(|p| p.below <= below).unwrap_or(false) {
+                return Err(anyhow::Error::msg("phases have to go from most health to least"));
+            }
+            a.phases.push(Phase { below, movement, fire });
+        },
+        "spawn" => a.spawn = if args.first() == Some(&"never") {
+            None
+        } else {
+            let v = floats(args, 4)?;
+            Some(Spawn { after: v[0], interval: v[1], duty_cycle: v[2], len: v[3] })
+        },
+        "entry" => a.entry = match args.first() {
+            Some(&"random") => Entry::Random,
+            Some(&"y") => Entry::Y(floats(&args[1..], 1)?[0]),
+            _ => return Err(anyhow::Error::msg("entry is random or y")),
+        },
+        "shape" => a.shapes.push(parse_shape(args)?),
+        w => return Err(anyhow::Error::msg(format!("dont know '{}'", w))),
+    }
+    Ok(())
+}
+
+fn parse_shape(args: &[&str]) -> Result<Shape, anyhow::Error> {
+    let (args, colour) = match args.iter().position(|w| *w == "colour") {
+        Some(i) => (&args[..i], Some(self::colour(&args[i+1..])?)),
+        None => (args, None),
+    };
+    let kind = args.first().ok_or_else(|| anyhow::Error::msg("shape needs a kind"))?;
+    let args = &args[1..];
+    let kind = match *kind {
+        "rect" => {
+            let v = floats(args, 4)?;
+            ShapeKind::Rect { c: v2(v[0], v[1]), w: v[2], h: v[3] }
+        },
+        "circle" => {
+            let v = floats(args, 3)?;
+            ShapeKind::Circle { c: v2(v[0], v[1]), r: v[2] }
+        },
+        "tri" => {
+            let v = floats(args, 6)?;
+            ShapeKind::Tri { a: v2(v[0], v[1]), b: v2(v[2], v[3]), c: v2(v[4], v[5]) }
+        },
+        "vpill" => {
+            let v = floats(args, 4)?;
+            ShapeKind::VPill { c: v2(v[0], v[1]), w: v[2], h: v[3] }
+        },
+        "orbit" => {
+            let v = floats(args, 4)?;
+            ShapeKind::Orbit { n: v[0] as u32, dist: v[1], r: v[2], speed: v[3] }
+        },
+        k => return Err(anyhow::Error::msg(format!("dont know shape '{}'", k))),
+    };
+    Ok(Shape { kind, colour })
+}
+
+fn colour(args: &[&str]) -> Result<Vec4, anyhow::Error> {
+    let v = floats(args, 3)?;
+    Ok(Vec4::new(v[0], v[1], v[2], 1.0))
+}
+
+fn floats(args: &[&str], n: usize) -> Result<Vec<f32>, anyhow::Error> {
+    if args.len() != n {
+        return Err(anyhow::Error::msg(format!("wanted {} numbers, got {}", n, args.len())));
+    }
+    args.iter().map(|w| w.parse::<f32>().map_err(|_| anyhow::Error::msg(format!("'{}' isnt a number", w)))).collect()
+}
+
+// the text of enemies.txt if its there and makes sense, otherwise the built in one,
+// so whatever comes out of this always parses
+pub fn load_archetype_source() -> String {
+    if let Ok(s) = std::fs::read_to_string(ARCHETYPE_FILE) {
+        match parse_archetypes(&s) {
+            Ok(_) => return s,
+            Err(e) => println!("{}: {}, using built in enemies", ARCHETYPE_FILE, e),
+        }
+    }
+    BUILTIN.to_owned()
+}
+
+pub fn load_archetypes() -> Vec<Archetype> {
+    parse_archetypes(&load_archetype_source()).expect("checked when it was loaded")
+}
+
+// games go through load_archetype_source, this is for tests that want the real enemies
+#[cfg(test)]
+pub fn builtin_archetypes() -> Vec<Archetype> {
+    parse_archetypes(BUILTIN).expect("built in enemies.txt is broken")
+}
+
+#[test]
+fn test_parse_archetypes() {
+    let archetypes = builtin_archetypes();
+    assert_eq!(archetypes.len(), 5);
+    assert_eq!(archetypes[4].spawn, None);
+    let boss = &archetypes[4];
+    assert_eq!(boss.phase(boss.health), 0);
+    assert_eq!(boss.phase(boss.health * 0.5), 1);
+    assert_eq!(boss.phase(boss.health * 0.1), 2);
+    assert_eq!(boss.movement(1), &boss.movement);
+    assert!(matches!(boss.fire(2).unwrap().spread, Spread::Spiral { .. }));
+    assert_eq!(archetypes[1].entry, Entry::Y(0.5));
+    assert_eq!(archetypes[3].fire, None);
+    assert_eq!(archetypes[0].shapes[4].colour, Some(Vec4::new(0.5, 0.8, 1.0, 1.0)));
+    assert!(archetypes[3].round);
+    assert_eq!(boss.points, 1000);
+
+    // a ray can clip the corner of a round ones box without hitting it
+    let orb = &archetypes[3];
+    let corner = v2(0.5 - orb.w/2.0, 0.5 - orb.w/2.0);
+    assert_eq!(orb.ray_hit(v2(0.5, 0.5), corner + v2(-0.01, 0.011), v2(1.0, -1.0).normalize()), None);
+    let (d, hurts) = orb.ray_hit(v2(0.5, 0.5), v2(0.0, 0.5), v2(1.0, 0.0)).unwrap();
+    assert!((d - (0.5 - orb.w/2.0)).abs() < 0.0001 && hurts);
+    // the boss stops it on a weak point or its armour
+    assert_eq!(boss.ray_hit(v2(0.5, 0.5), v2(0.0, 0.53), v2(1.0, 0.0)).map(|h| h.1), Some(true));
+    assert_eq!(boss.ray_hit(v2(0.5, 0.5), v2(0.0, 0.45), v2(1.0, 0.0)).map(|h| h.1), Some(false));
+
+    let e = parse_archetypes("enemy a\n  size 1\n").unwrap_err();
+    assert!(e.to_string().starts_with("line 2"));
+    assert!(parse_archetypes("speed 1\n").is_err());
+}
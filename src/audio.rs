@@ -194,7 +194,7 @@ where
     T: cpal::Sample,
     F: FnMut(&mut SampleRequestOptions) -> f32 + std::marker::Send + 'static,
 {
-    if let Some(sc) = request.channel.pop() {
+    while let Some(sc) = request.channel.pop() {
         request.audio_state.handle_event(sc);
     }
     for frame in output.chunks_mut(request.nchannels) {
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use glutin::event::{MouseButton, VirtualKeyCode};
+use gilrs::Button;
+
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+
+// Simulation runs at a fixed rate no matter how fast frames come in, so a hitch
+// doesnt change how far things move and the same inputs always give the same game.
+// Whatever time is left over goes to the frame as inputs.alpha for interpolating.
+// If an update asks for a hit-stop no steps happen for that long, frames still do.
+// Speed below 1 slows everything down without changing the step size.
+// Anything that went down on a frame without a step (fast monitor, hit-stop) is
+// kept until the next step so it still gets seen as a press.
+
+pub const SIM_DT: f32 = 1.0 / 120.0;
+
+// after a big hitch just drop the time instead of trying to catch up on all of it
+const MAX_FRAME_DT: f32 = 0.25;
+
+pub struct FixedStep {
+    acc: f32,
+    t: f32,
+    step: u32,
+    step_keys: HashSet<VirtualKeyCode>,
+    // same again for each pad
+    step_buttons: Vec<HashSet<Button>>,
+    // went down since the last step, out of FrameInputs::events
+    pending_keys: HashSet<VirtualKeyCode>,
+    pending_buttons: Vec<HashSet<Button>>,
+    pending_mouse: Vec<MouseButton>,
+    hit_stop: f32,
+    pub speed: f32,
+}
+
+impl FixedStep {
+    pub fn new() -> FixedStep {
+        FixedStep {
+            acc: 0.0,
+            t: 0.0,
+            step: 0,
+            step_keys: HashSet::new(),
+            step_buttons: vec![HashSet::new(); MAX_PADS],
+            pending_keys: HashSet::new(),
+            pending_buttons: vec![HashSet::new(); MAX_PADS],
+            pending_mouse: Vec::new(),
+            hit_stop: 0.0,
+            speed: 1.0,
+        }
+    }
+
+    pub fn frame(&mut self, demo: &mut dyn Demo, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        if self.hit_stop > 0.0 {
+            self.hit_stop -= inputs.dt;
+            self.collect(inputs);
+        } else {
+            self.update(demo, inputs, outputs);
+            self.hit_stop = outputs.hit_stop;
+        }
+
+        let mut frame_inputs = inputs.clone();
+        frame_inputs.alpha = self.acc / SIM_DT;
+        demo.frame(&frame_inputs, outputs);
+    }
+
+    fn collect(&mut self, inputs: &FrameInputs) {
+        for e in inputs.events.iter() {
+            match e.kind {
+                InputEventKind::KeyDown(k) => { self.pending_keys.insert(k); },
+                InputEventKind::MouseDown(m) => self.pending_mouse.push(m),
+                InputEventKind::PadDown(i, b) => if let Some(pending) = self.pending_buttons.get_mut(i) { pending.insert(b); },
+                _ => {},
+            }
+        }
+    }
+
+    // just the simulation steps for this much time, nothing gets drawn
+    pub fn update(&mut self, demo: &mut dyn Demo, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        self.acc += inputs.dt.min(MAX_FRAME_DT) * self.speed;
+        self.collect(inputs);
+
+        // whatever went down since the last step counts as held for the first one
+        let mut step_inputs = inputs.clone();
+        step_inputs.curr_keys.extend(self.pending_keys.iter().copied());
+        for (pad, pending) in step_inputs.pads.iter_mut().zip(self.pending_buttons.iter()) {
+            pad.curr_buttons.extend(pending.iter().copied());
+        }
+        for m in self.pending_mouse.iter() {
+            if let Some(status) = step_inputs.mouse_button_mut(*m) {
+                *status = KeyStatus::JustPressed;
+            }
+        }
+        while self.acc >= SIM_DT {
+            self.acc -= SIM_DT;
+
+            // edges are relative to the last step, not the last frame, so a press
+            // on a frame where no step happens still shows up as rising on the next one.
+            // if it went down again it must have come up since the last step saw it
+            step_inputs.prev_keys = std::mem::replace(&mut self.step_keys, step_inputs.curr_keys.clone());
+            step_inputs.prev_keys.retain(|k| !self.pending_keys.contains(k));
+            for ((pad, step_buttons), pending) in step_inputs.pads.iter_mut().zip(self.step_buttons.iter_mut()).zip(self.pending_buttons.iter()) {
+                pad.prev_buttons = std::mem::replace(step_buttons, pad.curr_buttons.clone());
+                pad.prev_buttons.retain(|b| !pending.contains(b));
+            }
+            self.pending_keys.clear();
+            self.pending_buttons.iter_mut().for_each(|p| p.clear());
+            self.pending_mouse.clear();
+            step_inputs.dt = SIM_DT;
+            step_inputs.t = self.t;
+            step_inputs.frame = self.step;
+            step_inputs.seed = khash(self.step);
+            step_inputs.alpha = 1.0;
+
+            demo.update(&step_inputs, outputs);
+
+            self.t += SIM_DT;
+            self.step += 1;
+
+            // the rest of the per frame stuff only goes to the first step, and
+            // after that whats held is just whats held now
+            step_inputs.repeat_keys = HashSet::new();
+            step_inputs.scroll_delta = 0.0;
+            step_inputs.mouse_delta = Vec2::new(0.0, 0.0);
+            step_inputs.curr_keys = inputs.curr_keys.clone();
+            for (pad, frame_pad) in step_inputs.pads.iter_mut().zip(inputs.pads.iter()) {
+                pad.curr_buttons = frame_pad.curr_buttons.clone();
+            }
+            step_inputs.lmb = settled(inputs.lmb);
+            step_inputs.mmb = settled(inputs.mmb);
+            step_inputs.rmb = settled(inputs.rmb);
+        }
+    }
+}
+
+fn settled(status: KeyStatus) -> KeyStatus {
+    match status {
+        KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed,
+        KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released,
+    }
+}
+
+#[test]
+fn test_pending_presses() {
+    #[derive(Default)]
+    struct Presses {
+        keys: u32,
+        clicks: u32,
+    }
+    impl Demo for Presses {
+        fn update(&mut self, inputs: &FrameInputs, _outputs: &mut FrameOutputs) {
+            self.keys += inputs.key_rising(VirtualKeyCode::Space) as u32;
+            self.clicks += (inputs.lmb == KeyStatus::JustPressed) as u32;
+        }
+        fn frame(&mut self, _inputs: &FrameInputs, _outputs: &mut FrameOutputs) {}
+    }
+    let mut stepper = FixedStep::new();
+    let mut demo = Presses::default();
+    let mut outputs = FrameOutputs::new(1.0);
+    let frame = |dt: f32, tap: bool| {
+        let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), 1);
+        inputs.dt = dt;
+        if tap {
+            inputs.events.push(InputEvent { t: 0.0, kind: InputEventKind::KeyDown(VirtualKeyCode::Space) });
+            inputs.events.push(InputEvent { t: 0.0, kind: InputEventKind::MouseDown(MouseButton::Left) });
+        }
+        inputs
+    };
+
+    // too short a frame for a step, the next one still gets the tap
+    stepper.frame(&mut demo, &frame(SIM_DT / 4.0, true), &mut outputs);
+    assert_eq!(demo.keys, 0);
+    stepper.frame(&mut demo, &frame(SIM_DT, false), &mut outputs);
+    assert_eq!((demo.keys, demo.clicks), (1, 1));
+
+    // same during a hit-stop
+    stepper.hit_stop = SIM_DT / 2.0;
+    stepper.frame(&mut demo, &frame(SIM_DT, true), &mut outputs);
+    assert_eq!(demo.keys, 1);
+    stepper.frame(&mut demo, &frame(SIM_DT, false), &mut outputs);
+    assert_eq!((demo.keys, demo.clicks), (2, 2));
+
+    // and only once when a frame has a few steps in it
+    stepper.frame(&mut demo, &frame(SIM_DT * 3.5, true), &mut outputs);
+    assert_eq!((demo.keys, demo.clicks), (3, 3));
+}
@@ -6,172 +6,613 @@ use crate::kmath::*;
 use crate::texture_buffer::*;
 use crate::audio::*;
 use crate::sound_instance::*;
-use glutin::event::VirtualKeyCode;
+use crate::archetype::*;
+use crate::stage::*;
+use crate::pattern::*;
+use crate::weapon::*;
+use crate::powerup::*;
+use crate::particles::*;
+use crate::juice::*;
+use crate::settings::*;
+use crate::actions::*;
+use crate::highscore::RunSummary;
+
+const PLAYER_W: f32 = 0.04;
+const PLAYER_H: f32 = 0.025;
+const PLAYER_SPEED: f32 = 0.5;
+
+const RESPAWN_TIME: f32 = 1.0;
+const INVULN_TIME: f32 = 2.0;
+
+const POWERUP_SPEED: f32 = 0.4;     // or as a drop from the purple guys
+                                    // or a powerup laser that kill bullets too
+const SHIELD_INVULN: f32 = 0.5;
+
+// kills within COMBO_WINDOW of each other keep a combo going, every COMBO_STEP
+// kills in it adds one to the multiplier
+const COMBO_WINDOW: f32 = 2.0;
+const COMBO_STEP: u32 = 5;
+const MAX_COMBO: i32 = 8;
+const POPUP_TIME: f32 = 0.8;
+
+const BULLET_W: f32 = 0.03;
+const BULLET_H: f32 = 0.01;
+
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayerInput {
+    pub steer: Vec2,
+    pub fire: bool,
+    // next weapon
+    pub switch: bool,
+}
+
+impl PlayerInput {
+    pub const IDLE: PlayerInput = PlayerInput { steer: Vec2 { x: 0.0, y: 0.0 }, fire: false, switch: false };
+
+    // stick only counts when none of the move actions are down, and goes
+    // partway as fast if its pushed partway
+    pub fn read(inputs: &FrameInputs, map: &ActionMap) -> PlayerInput {
+        let axis = |neg: Action, pos: Action| {
+            if inputs.action_held(map, neg) {
+                -1.0
+            } else if inputs.action_held(map, pos) {
+                1.0
+            } else {
+                0.0
+            }
+        };
+        let mut steer = Vec2::new(axis(Action::MoveLeft, Action::MoveRight), axis(Action::MoveUp, Action::MoveDown));
+        if steer == Vec2::new(0.0, 0.0) {
+            if let Some(pad) = inputs.pad(map.pad) {
+                steer = pad.left();
+            }
+        }
+        PlayerInput {
+            steer,
+            fire: inputs.action_held(map, Action::Fire),
+            switch: inputs.action_held(map, Action::Switch),
+        }
+    }
+}
+
+// everything the simulation gets to know about the outside world for one step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GameInput {
+    pub dt: f32,
+    pub screen_rect: Rect,
+    pub players: [PlayerInput; MAX_PLAYERS],
+    pub restart: bool,
+}
+
+impl GameInput {
+    pub fn from_frame(inputs: &FrameInputs, bindings: &[ActionMap; MAX_PLAYERS]) -> GameInput {
+        let mut players = [PlayerInput::IDLE; MAX_PLAYERS];
+        for (input, map) in players.iter_mut().zip(bindings.iter()) {
+            *input = PlayerInput::read(inputs, map);
+        }
+        GameInput {
+            dt: inputs.dt,
+            screen_rect: inputs.screen_rect,
+            players,
+            restart: bindings.iter().any(|map| inputs.action_rising(map, Action::Restart)),
+        }
+    }
+}
+
+// how many hits each player can take. a hit takes off one health, running out
+// of health loses a life and running out of lives is that player out. with shared
+// lives everyone loses lives from the same pool
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rules {
+    pub lives: u32,
+    pub health: u32,
+    pub players: u32,
+    pub shared_lives: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules { lives: 3, health: 1, players: 1, shared_lives: false }
+    }
+}
+
+impl Rules {
+    // --lives N, --health N or --hardcore for one hit and thats it,
+    // --players N and --shared-lives
+    pub fn from_args(args: &[String]) -> Rules {
+        let mut rules = Rules::default();
+        if let Some(lives) = crate::arg_value(args, "--lives") {
+            rules.lives = lives.parse::<u32>().expect("bad --lives").max(1);
+        }
+        if let Some(health) = crate::arg_value(args, "--health") {
+            rules.health = health.parse::<u32>().expect("bad --health").max(1);
+        }
+        if args.iter().any(|a| a == "--hardcore") {
+            rules.lives = 1;
+            rules.health = 1;
+        }
+        if let Some(players) = crate::arg_value(args, "--players") {
+            rules.players = players.parse::<u32>().expect("bad --players").clamp(1, MAX_PLAYERS as u32);
+        }
+        rules.shared_lives = args.iter().any(|a| a == "--shared-lives");
+        rules
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub pos: Vec2,
+    pub v: Vec2,
+    pub arsenal: Arsenal,
+    pub effects: Effects,
+    // where the laser got stopped last step
+    pub laser_hit: Option<Vec2>,
+    // false once its game over
+    pub alive: bool,
+    pub lives: u32,
+    pub health: u32,
+    pub t_died: f32,
+    pub respawn_at: Option<f32>,
+    pub invuln_until: f32,
+    pub score: i32,
+    pub combo: u32,
+    pub t_last_kill: f32,
+}
+
+impl Player {
+    pub fn new(pos: Vec2, rules: Rules) -> Player {
+        Player {
+            pos,
+            v: Vec2::new(0.0, 0.0),
+            arsenal: Arsenal::default(),
+            effects: Effects::default(),
+            laser_hit: None,
+            alive: true,
+            lives: rules.lives,
+            health: rules.health,
+            t_died: -10.0,
+            respawn_at: None,
+            invuln_until: 0.0,
+            score: 0,
+            combo: 0,
+            t_last_kill: -10.0,
+        }
+    }
+
+    // where the i'th of n starts and respawns
+    pub fn start_pos(i: usize, n: usize, h: f32) -> Vec2 {
+        Vec2::new(0.1, h * (i + 1) as f32 / (n + 1) as f32)
+    }
+
+    pub fn colour(i: usize) -> Vec4 {
+        let hue = [120.0, 200.0, 40.0, 300.0][i % MAX_PLAYERS];
+        v4(hue, 0.5, 1.0, 1.0).hsv_to_rgb()
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.pos.rect_centered(PLAYER_W, PLAYER_H)
+    }
+
+    // flying around, ie not blown up
+    pub fn in_play(&self) -> bool {
+        self.alive && self.respawn_at.is_none()
+    }
+
+    pub fn combo_multiplier(&self) -> i32 {
+        (1 + (self.combo / COMBO_STEP) as i32).min(MAX_COMBO)
+    }
+
+    pub fn vulnerable(&self, t: f32) -> bool {
+        self.in_play() && t >= self.invuln_until
+    }
+
+    // returns the sound it makes
+    pub fn hit(&mut self, t: f32, rules: Rules) -> u32 {
+        if self.effects.take(PowerupKind::Shield) {
+            self.invuln_until = t + SHIELD_INVULN;
+            return LASER_POP;
+        }
+        self.health -= 1;
+        if self.health > 0 {
+            self.invuln_until = t + INVULN_TIME;
+            return LASER_POP;
+        }
+        self.lives = self.lives.saturating_sub(1);
+        self.t_died = t;
+        self.arsenal.beam = None;
+        self.laser_hit = None;
+        self.effects = Effects::default();
+        self.combo = 0;
+        if self.lives == 0 {
+            self.alive = false;
+        } else {
+            self.respawn_at = Some(t + RESPAWN_TIME);
+            self.health = rules.health;
+        }
+        PLAYER_DIE
+    }
+}
+
+fn player_tri(p: Vec2) -> Triangle {
+    let ptl = Vec2::new(p.x - PLAYER_W/2.0, p.y + PLAYER_H/2.0);
+    let pbl = Vec2::new(p.x - PLAYER_W/2.0, p.y - PLAYER_H/2.0);
+    let pr = Vec2::new(p.x + PLAYER_W/2.0, p.y);
+    Triangle::new(ptl, pr, pbl)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Enemy {
+    pub pos: Vec2,
+    pub v: Vec2,
+    pub kind: usize,
+    pub dob: f32,
+    pub t_last_shoot: f32,
+    pub seed: u32,
+    pub path: Option<Path>,
+    pub boss: bool,
+    pub health: f32,
+    pub phase: usize,
+    pub t_hit: f32,
+    pub last_hit_by: usize,
+    pub shots: u32,
+}
+
+impl Enemy {
+    pub fn rect(&self, archetypes: &[Archetype]) -> Rect {
+        let a = &archetypes[self.kind];
+        self.pos.rect_centered(a.w, a.h)
+    }
+
+    // returns true if that knocked it into its next phase
+    pub fn damage(&mut self, a: &Archetype, amount: f32, by: usize, t: f32) -> bool {
+        self.health -= amount;
+        self.t_hit = t;
+        self.last_hit_by = by;
+        let phase = a.phase(self.health);
+        if phase != self.phase && self.health > 0.0 {
+            self.phase = phase;
+            self.t_last_shoot = t;
+            return true;
+        }
+        false
+    }
+}
+
+// points floating up from where something died
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScorePopup {
+    pub pos: Vec2,
+    pub points: i32,
+    pub owner: usize,
+    pub t: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Powerup {
+    pub pos: Vec2,
+    pub kind: PowerupKind,
+}
 
 pub struct Game {
-    player_x: f32,
-    player_y: f32,
-    player_laser_heat: f32,
-    player_laser_discharge: bool,
-    player_alive: bool,
-
-    enemies_pos: Vec<Vec2>,
-    enemies_v: Vec<Vec2>,
-    enemies_t_last_shoot: Vec<f32>,
-    enemies_type: Vec<usize>,
-    enemies_dob: Vec<f32>,
-    bullets_pos: Vec<Vec2>,
+    archetypes: Vec<Archetype>,
+    archetype_source: String,
+    players: Vec<Player>,
+    enemies: Vec<Enemy>,
+    bullets: Vec<Bullet>,
+    shots: Vec<Shot>,
+    popups: Vec<ScorePopup>,
+    particles: Particles,
+    juice: Juice,
+    // how its drawn and read, nothing in here changes the simulation
+    settings: Settings,
+    // from the pause menu, goes in with the next step
+    restart_pending: bool,
     
     powerup_t_last: f32,
-    powerup_collect_t_last: f32,
-    powerup_pos: Option<Vec2>,
+    powerup: Option<Powerup>,
     powerup_number: u32,
 
     t: f32,
-    t_last_spawn: [f32; 4],
+    t_dead: f32,
+    dt_last: f32,
+    t_last_spawn: Vec<f32>,
+    spawn_number: u32,
     seed: u32,
+    screen_rect: Rect,
+    rules: Rules,
 
-    score: i32,
+    // the stage being played, if theres no stage its endless
+    stage: Option<StageRun>,
+
+    // sounds from the last update
+    pub audio_events: Vec<u32>,
 }
 
 impl Default for Game {
     fn default() -> Self {
+        Game::new(random_seed())
+    }
+}
+
+pub fn random_seed() -> u32 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|x| x.as_nanos() as u32).unwrap_or(1)
+}
+
+impl Game {
+    pub fn new(seed: u32) -> Game {
+        Game::with_archetype_source(seed, load_archetype_source())
+    }
+
+    // the source has to parse, load_archetype_source and replays check it first
+    pub fn with_archetype_source(seed: u32, source: String) -> Game {
+        let mut game = Game::with_archetypes(seed, parse_archetypes(&source).expect("enemy types dont parse"));
+        game.archetype_source = source;
+        game
+    }
+
+    pub fn with_archetypes(seed: u32, archetypes: Vec<Archetype>) -> Game {
         Game {
-            player_x: 0.1,
-            player_y: 0.5,
-            player_alive: true,
-            player_laser_heat: 0.0,
-            player_laser_discharge: false,
-            enemies_pos: Vec::new(),
-            enemies_t_last_shoot: Vec::new(),
-            enemies_v: Vec::new(),
-            enemies_type: Vec::new(),
-            enemies_dob: Vec::new(),
-            bullets_pos: Vec::new(),
-            powerup_pos: None,
+            t_last_spawn: vec![0.0; archetypes.len()],
+            archetypes,
+            archetype_source: String::new(),
+            players: vec![Player::new(Player::start_pos(0, 1, 1.0), Rules::default())],
+            enemies: Vec::new(),
+            bullets: Vec::new(),
+            shots: Vec::new(),
+            popups: Vec::new(),
+            particles: Particles::new(khash(seed)),
+            juice: Juice::new(seed),
+            settings: Settings::default(),
+            restart_pending: false,
+            powerup: None,
             powerup_t_last: 0.0,
-            powerup_collect_t_last: -10.0,
             powerup_number: 0,
             t: 0.0,
-            t_last_spawn: [0.0; 4],
-            seed: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|x| x.as_nanos() as u32).unwrap_or(1),
-            score: 0,
+            t_dead: 0.0,
+            dt_last: 0.0,
+            spawn_number: 0,
+            seed,
+            screen_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+            rules: Rules::default(),
+            stage: None,
+            audio_events: Vec::new(),
         }
     }
-}
 
-impl Demo for Game {
-    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
-        if inputs.key_rising(VirtualKeyCode::R) {
-            *self = Game::default();
-            outputs.audio_events.push(LASER);
-            outputs.audio_events.push(ENEMY_DIE);
-            outputs.audio_events.push(ENEMY_SHOOT);
-            outputs.audio_events.push(PLAYER_DIE);
+    pub fn with_stage(seed: u32, stage: Stage) -> Game {
+        let mut game = Game::new(seed);
+        game.set_stage(stage);
+        game
+    }
+
+    // only makes sense before it starts
+    pub fn set_stage(&mut self, stage: Stage) {
+        self.stage = Some(StageRun::new(stage));
+    }
+
+    // what enemies.txt said when this game started, replays keep a copy
+    pub fn archetype_source(&self) -> &str {
+        &self.archetype_source
+    }
+
+    // only makes sense before it starts
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        let n = rules.players as usize;
+        self.players = (0..n).map(|i| Player::new(Player::start_pos(i, n, self.screen_rect.h), rules)).collect();
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    // everyones put together
+    pub fn score(&self) -> i32 {
+        self.players.iter().map(|p| p.score).sum()
+    }
+
+    pub fn scores(&self) -> Vec<i32> {
+        self.players.iter().map(|p| p.score).collect()
+    }
+
+    // until everyones out
+    pub fn alive(&self) -> bool {
+        self.players.iter().any(|p| p.alive)
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    // how long the player has survived so far
+    pub fn t(&self) -> f32 {
+        self.t
+    }
+
+    pub fn cleared(&self) -> bool {
+        self.stage.as_ref().map(|run| run.t_cleared.is_some()).unwrap_or(false)
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        self.juice.intensity = settings.fx;
+        self.juice.flashes = settings.flash_strength();
+    }
+
+    pub fn take_hit_stop(&mut self) -> f32 {
+        self.juice.take_hit_stop()
+    }
+
+    pub fn stage(&self) -> Option<&Stage> {
+        self.stage.as_ref().map(|run| &run.stage)
+    }
+
+    fn spawn_seed(&self) -> u32 {
+        khash(self.seed.wrapping_add(self.spawn_number.wrapping_mul(1231247)))
+    }
+
+    fn spawn_enemy(&mut self, kind: usize, pos: Vec2, path: Option<Path>, boss: bool) {
+        let spawn_seed = self.spawn_seed();
+        self.enemies.push(Enemy {
+            pos,
+            v: Vec2::new(-self.archetypes[kind].speed, 0.0),
+            kind,
+            dob: self.t,
+            t_last_shoot: self.t,
+            seed: khash(spawn_seed),
+            path,
+            boss,
+            // health 0 is dies on touch, so it starts just above dead
+            health: self.archetypes[kind].health.max(f32::MIN_POSITIVE),
+            phase: 0,
+            t_hit: -10.0,
+            last_hit_by: 0,
+            shots: 0,
+        });
+        self.spawn_number += 1;
+        self.audio_events.push(SOUND_PLAY | ENEMY_SPAWN);
+    }
+
+    fn hit_player(&mut self, i: usize) {
+        if !self.players[i].vulnerable(self.t) {
+            return;
+        }
+        let sound = self.players[i].hit(self.t, self.rules);
+        if sound == PLAYER_DIE {
+            self.particles.emit(&PLAYER_DEATH, self.players[i].pos, 0.0);
+            self.juice.add_trauma(0.8);
+            self.juice.hit_stop(0.15);
+            self.juice.flash(v4(1.0, 0.0, 0.0, 0.5), 0.25);
+        } else {
+            self.juice.add_trauma(0.4);
         }
+        if self.rules.shared_lives {
+            let lives = self.players[i].lives;
+            for player in self.players.iter_mut() {
+                player.lives = lives;
+            }
+        }
+        self.audio_events.push(SOUND_PLAY | sound);
+    }
 
-        let player_w = 0.04;
-        let player_h = 0.025;
-        let player_speed = 0.5;
-        
-        let laser_h = 0.005;
-        
-        let player_border_colour = v4(0.0, 0.0, 0.0, 1.0);
-        let player_inner_colour = v4(120.0, 0.5, 1.0, 1.0).hsv_to_rgb();
-        let laser_colour = v4(1.0, 0.0, 0.0, 1.0);
-        
-        let enemy_w = [0.05, 0.04, 0.03, 0.05];
-        let enemy_h = [0.05, 0.04, 0.03, 0.05];
-        let enemy_shoot_interval = [1.41, 0.8, 0.7, f32::INFINITY];
-        let enemy_spawn_interval = [1.0, 0.2, 0.1, 5.0];
-        let enemy_spawn_after = [0.0, 10.0, 20.0, 30.0];
-        let enemy_spawn_duty_cycle = [1.0, 0.21, 0.1, 1.0];
-        let enemy_spawn_len = [1.0, 3.0, 0.6, 0.1];
-        let enemy_speed = [0.1, 0.2, 0.25, 0.4];
-        let enemy_colour = [v4(0.0, 0.0, 1.0, 1.0), v4(1.0, 0.0, 0.0, 1.0), v4(0.0, 0.9, 0.0, 1.0), v4(0.7, 0.0, 0.7, 1.0)];
-
-        let powerup_speed = 0.4;    // or as a drop from the purple guys
-                                    // or a powerup laser that kill bullets too
-                                    // or kill all bullets powerup, this is powerup to kill all enemies
+    fn spawn_stage_event(&mut self, event: &SpawnEvent) {
+        let kind = match self.archetypes.iter().position(|a| a.name == event.archetype) {
+            Some(kind) => kind,
+            None => return,
+        };
+        let a = &self.archetypes[kind];
+        let y = match event.entry.as_ref().unwrap_or(&a.entry) {
+            Entry::Random => krand(self.spawn_seed()),
+            Entry::Y(y) => *y,
+        };
+        let entry = Vec2::new(self.screen_rect.w + a.w/2.0, y);
+        for offset in event.formation.offsets() {
+            let pos = entry + offset;
+            self.spawn_enemy(kind, pos, event.path.as_ref().map(|p| p.moved(pos)), event.boss);
+        }
+    }
+
+    pub fn update(&mut self, inputs: &GameInput) {
+        self.audio_events.clear();
 
-        let bullet_w = 0.03;
-        let bullet_h = 0.01;
-        let bullet_colour = Vec4::new(1.0, 1.0, 0.0, 1.0);
-        let bullet_speed = 0.6;
+        if inputs.restart {
+            // dying in a stage goes back to the last checkpoint, beating it starts it over
+            let stage = self.stage.as_ref().map(|run| if run.t_cleared.is_some() { StageRun::new(run.stage.clone()) } else { run.restarted_at_checkpoint() });
+            let rules = self.rules;
+            let settings = self.settings;
+            // next seed comes from this one and the enemy types carry over, so a
+            // replayed restart stays on the same run
+            let archetypes = std::mem::take(&mut self.archetypes);
+            let source = std::mem::take(&mut self.archetype_source);
+            *self = Game::with_archetypes(khash(self.seed), archetypes);
+            self.archetype_source = source;
+            self.set_rules(rules);
+            self.set_settings(settings);
+            if let Some(run) = stage {
+                self.t = run.checkpoint_t();
+                for (player, score) in self.players.iter_mut().zip(run.checkpoint_scores.iter()) {
+                    player.score = *score;
+                }
+                self.powerup_t_last = self.t;
+                self.stage = Some(run);
+            }
+            self.audio_events.push(LASER);
+            self.audio_events.push(ENEMY_DIE);
+            self.audio_events.push(ENEMY_SHOOT);
+            self.audio_events.push(PLAYER_DIE);
+            self.audio_events.push(CHARGE);
+        }
 
+        self.screen_rect = inputs.screen_rect;
         let mut dt = inputs.dt;
 
 
         /////////////////////////////////////////////
         // Player
         /////////////////////////////////////////////
-        if self.player_alive {
+        if self.alive() {
             self.t += inputs.dt;
         } else {
+            self.t_dead += inputs.dt;
             dt = 0.0;
         }
+        self.dt_last = dt;
 
-        let steer_y = if inputs.key_held(VirtualKeyCode::W) {
-            -1.0
-        } else if inputs.key_held(VirtualKeyCode::S) {
-            1.0
-        } else {
-            0.0
-        };
-            
-        let steer_x = if inputs.key_held(VirtualKeyCode::D) {
-            1.0
-        } else if inputs.key_held(VirtualKeyCode::A) {
-            -1.0
-        } else {
-            0.0
-        };
-
-        let steer = Vec2::new(steer_x, steer_y).normalize();
-        let v = steer * player_speed * inputs.dt;
-        self.player_x += v.x;
-        self.player_y += v.y;
-
-        let player_rect = Rect::new_centered(self.player_x, self.player_y, player_w, player_h);
+        let n = self.players.len();
+        for (i, player) in self.players.iter_mut().enumerate() {
+            player.effects.expire(self.t);
+            if self.t - player.t_last_kill > COMBO_WINDOW {
+                player.combo = 0;
+            }
+            if player.respawn_at.map(|t| self.t >= t).unwrap_or(false) {
+                player.respawn_at = None;
+                player.pos = Player::start_pos(i, n, self.screen_rect.h);
+                player.invuln_until = self.t + INVULN_TIME;
+            }
 
-        let ptl = Vec2::new(self.player_x - player_w/2.0, self.player_y + player_h/2.0);
-        let pbl = Vec2::new(self.player_x - player_w/2.0, self.player_y - player_h/2.0);
-        let pr = Vec2::new(self.player_x + player_w/2.0, self.player_y);
-        let ptri = Triangle::new(ptl, pr, pbl);
-        let ptri_inner = ptri.dilate(-0.3);
-        if self.player_alive {
-            outputs.canvas.put_triangle_struct(ptri, 1.6, player_border_colour);
-            outputs.canvas.put_triangle_struct(ptri_inner, 1.7, player_inner_colour);
+            if player.in_play() {
+                // keys are all or nothing, a stick partway over goes partway as fast
+                let steer = inputs.players[i].steer;
+                let steer = if steer.magnitude() > 1.0 { steer.normalize() } else { steer };
+                player.v = steer * PLAYER_SPEED * player.effects.speed();
+            } else {
+                player.v = Vec2::new(0.0, 0.0);
+            }
+            player.pos = player.pos + player.v * inputs.dt;
+            if player.in_play() && dt > 0.0 {
+                self.particles.emit(&EXHAUST, player.pos - v2(PLAYER_W/2.0, 0.0), PI);
+            }
         }
+        // these keep going once everyones dead so the last explosion finishes
+        self.particles.update(inputs.dt);
+        self.juice.update(inputs.dt);
 
 
         ///////////////////////////////////
-        // Laser
+        // Weapons
         ///////////////////////////////////
-        let shoot_laser = inputs.key_held(VirtualKeyCode::Space) && !self.player_laser_discharge && self.player_alive;
-        let laser_rect = if shoot_laser {
-            Some(Rect::new(self.player_x, self.player_y - laser_h/2.0, 100.0, laser_h))
-        } else {
-            None
-        };
-        if let Some(laser_rect) = laser_rect {
-            outputs.canvas.put_rect(laser_rect, 1.5, laser_colour);
-        }
-        if shoot_laser {
-            outputs.audio_events.push(SOUND_PLAY | LASER | SOUND_UNIQUE);
-            self.player_laser_heat += dt;
-            if self.player_laser_heat > 1.0 {
-                self.player_laser_heat = 1.0;
-                self.player_laser_discharge = true;
-                outputs.audio_events.push(LASER_POP | SOUND_PLAY);
+        // who let off a bomb and how much it hurts
+        let mut bombs: Vec<(usize, f32)> = Vec::new();
+        for (i, player) in self.players.iter_mut().enumerate() {
+            let input = inputs.players[i];
+            player.arsenal.cooled = player.effects.has(PowerupKind::Cooling);
+            let mut firing = player.arsenal.update(input.fire, input.switch, player.in_play(), self.t, dt, player.pos, i);
+            self.shots.append(&mut firing.shots);
+            if let Some(damage) = firing.bomb {
+                bombs.push((i, damage));
             }
+            self.audio_events.append(&mut firing.audio_events);
         }
-        if !shoot_laser {
-            outputs.audio_events.push(LASER);
-            self.player_laser_heat -= dt;
-            if self.player_laser_heat <= 0.0 {
-                self.player_laser_heat = 0.0;
-                self.player_laser_discharge = false;
+        // these keep going until theyre stopped
+        for sound in [LASER, CHARGE] {
+            if !self.audio_events.contains(&(SOUND_PLAY | SOUND_UNIQUE | sound)) {
+                self.audio_events.push(sound);
             }
         }
 
@@ -179,71 +620,101 @@ impl Demo for Game {
         /////////////////////////////////////////
         // Powerups
         /////////////////////////////////////////
-        if self.t - self.powerup_t_last > kuniform(1241235417 * self.powerup_number + 1234125417, 10.0, 20.0) && self.powerup_pos.is_none() {
-            let y = kuniform(1361723497 * self.powerup_number + 9323717, 0.0, 1.0);
-            let x = inputs.screen_rect.w + 0.05;
-            self.powerup_pos = Some(Vec2::new(x, y));
+        if self.t - self.powerup_t_last > kuniform(1241235417u32.wrapping_mul(self.powerup_number).wrapping_add(1234125417), 10.0, 20.0) && self.powerup.is_none() {
+            let y = kuniform(1361723497u32.wrapping_mul(self.powerup_number).wrapping_add(9323717), 0.0, 1.0);
+            let x = self.screen_rect.w + 0.05;
+            let kind = pick_powerup(khash(self.seed.wrapping_add(self.powerup_number.wrapping_mul(2654435761))));
+            self.powerup = Some(Powerup { pos: Vec2::new(x, y), kind });
             self.powerup_number += 1;
             self.powerup_t_last = self.t;
         }
 
         let mut pp_off = false;
-        let mut pp_collected = false;
-        if let Some(ppos) = self.powerup_pos.as_mut() {
-            ppos.x -= powerup_speed * dt;
-            if ppos.x < -0.1 {
+        // who got it
+        let mut pp_collected = None;
+        if let Some(powerup) = self.powerup.as_mut() {
+            powerup.pos.x -= POWERUP_SPEED * dt;
+            if powerup.pos.x < -0.1 {
                 pp_off = true;
             }
             let r = 0.03;
-            let pp_rect = Rect::new_centered(ppos.x, ppos.y, r, r);
-            if pp_rect.overlaps(player_rect).is_some() {
+            let pp_rect = powerup.pos.rect_centered(r, r);
+            if let Some(i) = self.players.iter().position(|p| p.in_play() && pp_rect.overlaps(p.rect()).is_some()) {
                 pp_off = true;
-                pp_collected = true;
-                self.powerup_collect_t_last = self.t;
-                outputs.audio_events.push(SOUND_PLAY | POWERUP);
+                pp_collected = Some((i, powerup.kind));
+                self.audio_events.push(SOUND_PLAY | powerup_type(powerup.kind).sound);
+                self.particles.emit(&POWERUP_PICKUP.with_colour(powerup_type(powerup.kind).colour), powerup.pos, 0.0);
             }
-            outputs.canvas.put_triangle(
-                r * Vec2::new((inputs.t * TAU/3.0).sin(), (inputs.t * TAU/3.0).cos()) + *ppos, 
-                r * Vec2::new((inputs.t * TAU/3.0 + TAU/3.0).sin(), (inputs.t * TAU/3.0  + TAU/3.0).cos()) + *ppos, 
-                r * Vec2::new((inputs.t * TAU/3.0 + 2.0 * TAU/3.0).sin(), (inputs.t * TAU/3.0  + 2.0 * TAU/3.0).cos()) + *ppos, 
-            3.0, Vec4::new(0.0, 1.0, 1.0, 1.0));
-            outputs.canvas.put_triangle(
-                r * Vec2::new((-inputs.t * TAU/3.0).sin(), (-inputs.t * TAU/3.0).cos()) + *ppos, 
-                r * Vec2::new((-inputs.t * TAU/3.0 + TAU/3.0).sin(), (-inputs.t * TAU/3.0  + TAU/3.0).cos()) + *ppos, 
-                r * Vec2::new((-inputs.t * TAU/3.0 + 2.0 * TAU/3.0).sin(), (-inputs.t * TAU/3.0  + 2.0 * TAU/3.0).cos()) + *ppos, 
-            2.9, Vec4::new(1.0, 1.0, 1.0, 1.0));
         }
 
         if pp_off {
-            self.powerup_pos = None;
+            self.powerup = None;
         }
-
-        if self.t - self.powerup_collect_t_last < 0.0707 {
-            outputs.canvas.put_rect(inputs.screen_rect, 1.4, v4(1., 1., 1., 1.));
+        match pp_collected {
+            Some((_, PowerupKind::ScreenClear)) => {
+                self.juice.flash(v4(1.0, 1.0, 1.0, 1.0), 0.15);
+                self.juice.add_trauma(0.5);
+            },
+            Some((i, PowerupKind::Upgrade)) => {
+                self.players[i].arsenal.upgrade();
+            },
+            Some((i, PowerupKind::ExtraLife)) => {
+                let lives = self.players[i].lives + 1;
+                for (j, player) in self.players.iter_mut().enumerate() {
+                    if j == i || self.rules.shared_lives {
+                        player.lives = lives;
+                    }
+                }
+            },
+            Some((i, kind)) => self.players[i].effects.add(kind, self.t),
+            None => {},
         }
+        // whoever cleared the screen gets the points
+        let pp_cleared = match pp_collected {
+            Some((i, PowerupKind::ScreenClear)) => Some(i),
+            _ => None,
+        };
         
 
 
         ////////////////////////////////////////////
         // Enemy Spawning
         ////////////////////////////////////////////
-        for et in 0..4 {
-            if self.t > enemy_spawn_after[et] && self.t - self.t_last_spawn[et] > enemy_spawn_interval[et] && ((self.t - enemy_spawn_after[et]) % (enemy_spawn_len[et]/enemy_spawn_duty_cycle[et])) < enemy_spawn_len[et] {
-
-                let sp = [
-                    Vec2::new(inputs.screen_rect.w + enemy_w[et]/2.0, krand(inputs.seed)),
-                    Vec2::new(inputs.screen_rect.w + enemy_w[et]/2.0, 0.5),
-                    Vec2::new(inputs.screen_rect.w + enemy_w[et]/2.0, krand(inputs.seed)),
-                    Vec2::new(inputs.screen_rect.w + enemy_w[et]/2.0, krand(inputs.seed)),
-                ];
-
-                self.enemies_pos.push(sp[et]);
-                self.enemies_t_last_shoot.push(self.t);
-                self.enemies_v.push(Vec2::new(-enemy_speed[et], 0.0));
-                self.enemies_type.push(et);
-                self.enemies_dob.push(self.t);
-                self.t_last_spawn[et] = self.t;
-                outputs.audio_events.push(SOUND_PLAY | ENEMY_SPAWN);
+        if let Some(run) = self.stage.as_mut() {
+            let mut due = Vec::new();
+            if run.t_cleared.is_none() {
+                while let Some(event) = run.stage.events.get(run.next_event) {
+                    if event.t > self.t {
+                        break;
+                    }
+                    due.push(event.clone());
+                    run.next_event += 1;
+                }
+            }
+            while run.checkpoints_reached < run.stage.checkpoints.len() && run.stage.checkpoints[run.checkpoints_reached] <= self.t {
+                run.checkpoints_reached += 1;
+                run.checkpoint_scores = self.players.iter().map(|p| p.score).collect();
+                run.t_checkpoint = self.t;
+            }
+            for event in due.iter() {
+                self.spawn_stage_event(event);
+            }
+        } else {
+            for et in 0..self.archetypes.len() {
+                let a = &self.archetypes[et];
+                let sp = match a.spawn.as_ref() {
+                    Some(sp) => sp,
+                    None => continue,
+                };
+                if self.t > sp.after && self.t - self.t_last_spawn[et] > sp.interval && ((self.t - sp.after) % (sp.len/sp.duty_cycle)) < sp.len {
+                    let y = match a.entry {
+                        Entry::Random => krand(self.spawn_seed()),
+                        Entry::Y(y) => y,
+                    };
+                    let pos = Vec2::new(self.screen_rect.w + a.w/2.0, y);
+                    self.spawn_enemy(et, pos, None, false);
+                    self.t_last_spawn[et] = self.t;
+                }
             }
         }
 
@@ -251,86 +722,192 @@ impl Demo for Game {
         /////////////////////////////////////////////////
         // Enemy Movement
         /////////////////////////////////////////////////
-        for i in 0..self.enemies_v.len() {
-            let et = self.enemies_type[i];
-            let vy = [
-                0.1 * (noise1d(self.t, self.seed + i as u32 * 12312487) - 0.5) * 2.0,
-                -0.3 * (self.t - self.enemies_dob[i]).cos(),
-                0.3 * (noise1d(self.t, self.seed + i as u32 * 12312487) - 0.5) * 2.0,
-                0.3 * (10.0*(self.t - self.enemies_dob[i])).cos(),
-            ];
-            self.enemies_v[i].y = vy[et];
-            self.enemies_pos[i] = self.enemies_pos[i] + dt * self.enemies_v[i];
-            let p = self.enemies_pos[i];
-            let r = p.rect_centered(enemy_w[et], enemy_h[et]);
-            let c = enemy_colour[et];
-
-            match et {
-                0 => {
-                    let rh = 0.02;
-                    outputs.canvas.put_rect(p.rect_centered(0.05, rh), 1.5, c);
-                    outputs.canvas.put_circle(p + v2(0.0, -rh/2.0), 0.025, 1.5,  c);
-                    outputs.canvas.put_circle(p + v2(-0.05/4.0, rh/2.0), 0.025/2.0, 1.5,  c);
-                    outputs.canvas.put_circle(p + v2(0.05/4.0, rh/2.0), 0.025/2.0, 1.5,  c);
-                    outputs.canvas.put_circle(p + v2(0., -0.01), 0.025/2.5, 1.51,  v4(0.5, 0.8, 1., 1.));
-                },
-                1 => {
-                    let rh = 0.02;
-                    let rw = 0.02;
-                    outputs.canvas.put_triangle(p + v2(0., -rh), p + v2(rw, -rh), p + v2(rw, rh), 1.5, v4(1., 1., 0., 1.));
-                    outputs.canvas.put_triangle(p + v2(0., rh), p + v2(rw, -rh), p + v2(rw, rh), 1.5, v4(1., 1., 0., 1.));
-                    outputs.canvas.put_triangle(p + v2(-rw, 0.0), p + v2(rw, -rh), p + v2(rw, rh), 1.5, v4(1., 0., 0., 1.));
-                },
-                2 => {
-                    outputs.canvas.put_vpill(p, 0.03, 0.01, 1.5, c);
-                    outputs.canvas.put_vpill(p, 0.02, 0.08, 1.5, c);
-                    outputs.canvas.put_circle(p, 0.007, 1.51, v4(0.3, 0.3, 0.3, 1.0));
-                },
-                3 => {
-                    let r = 0.03;
-                    let phase = 2.0 * self.t;
-                    outputs.canvas.put_circle(p, r, 1.5, c);
-                    outputs.canvas.put_circle(p + 3.*r/2. * v2(phase.sin(), phase.cos()), r/2., 1.5, c);
-                    outputs.canvas.put_circle(p + 3.*r/2. * v2((phase + TAU/4.0).sin(), (phase + TAU/4.0).cos()), r/2., 1.5, c);
-                    outputs.canvas.put_circle(p + 3.*r/2. * v2((phase + 2.0*TAU/4.0).sin(), (phase + 2.0*TAU/4.0).cos()), r/2., 1.5, c);
-                    outputs.canvas.put_circle(p + 3.*r/2. * v2((phase + 3.0*TAU/4.0).sin(), (phase + 3.0*TAU/4.0).cos()), r/2., 1.5, c);
+        let boss_x = self.screen_rect.w * 0.75;
+        let mut hits = Vec::new();
+        for enemy in self.enemies.iter_mut() {
+            let a = &self.archetypes[enemy.kind];
+            let along_path = enemy.path.as_ref().map(|p| (self.t - enemy.dob) / p.duration).filter(|s| *s < 1.0);
+            if let (Some(s), Some(path)) = (along_path, enemy.path.as_ref()) {
+                let p = path.at(s);
+                if dt > 0.0 {
+                    enemy.v = (p - enemy.pos) / dt;
+                }
+                enemy.pos = p;
+            } else {
+                if enemy.path.take().is_some() {
+                    enemy.v.x = -a.speed;
                 }
-                _ => outputs.canvas.put_rect(r, 1.5, enemy_colour[et]),
-            }            
+                if enemy.boss && enemy.pos.x <= boss_x {
+                    enemy.v.x = 0.0;
+                }
+                enemy.v.y = a.vy(enemy.phase, self.t, enemy.dob, enemy.seed);
+                enemy.pos = enemy.pos + dt * enemy.v;
+            }
             
-            let r = r.dilate_pc(-0.2);
-            if r.overlaps(player_rect).is_some() && self.player_alive {
-                self.player_alive = false;
-                outputs.audio_events.push(SOUND_PLAY | PLAYER_DIE);
+            let r = enemy.rect(&self.archetypes).dilate_pc(-0.2);
+            for (i, player) in self.players.iter().enumerate() {
+                if r.overlaps(player.rect()).is_some() && player.vulnerable(self.t) {
+                    hits.push(i);
+                }
             }
         }
+        for i in hits.drain(..) {
+            self.hit_player(i);
+        }
+
+
+        ///////////////////////////////////////////
+        // Enemy Damage
+        ///////////////////////////////////////////
+        // the laser is a ray that stops at the first thing in the way unless its piercing,
+        // the charge beam goes through everything
+        for (i, player) in self.players.iter_mut().enumerate() {
+            player.laser_hit = None;
+            let beam = match player.arsenal.beam_at(self.t) {
+                Some(beam) => beam,
+                None => continue,
+            };
+            if beam.kind == WeaponKind::Laser && !player.effects.has(PowerupKind::Pierce) {
+                let dir = v2(1.0, 0.0);
+                let first = self.enemies.iter().enumerate()
+                    .filter_map(|(j, e)| self.archetypes[e.kind].ray_hit(e.pos, player.pos, dir).map(|(d, hurts)| (j, d, hurts)))
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                if let Some((j, d, hurts)) = first {
+                    player.laser_hit = Some(player.pos + d * dir);
+                    self.particles.emit(&SPARKS, player.pos + d * dir, PI);
+                    let enemy = &mut self.enemies[j];
+                    if hurts && enemy.damage(&self.archetypes[enemy.kind], beam.dps * dt, i, self.t) {
+                        self.audio_events.push(SOUND_PLAY | LASER_POP);
+                    }
+                }
+                continue;
+            }
+            let beam_rect = beam.rect(player.pos);
+            for enemy in self.enemies.iter_mut() {
+                let a = &self.archetypes[enemy.kind];
+                if a.hit_by(enemy.pos, beam_rect) && enemy.damage(a, beam.dps * dt, i, self.t) {
+                    self.audio_events.push(SOUND_PLAY | LASER_POP);
+                }
+            }
+        }
+
+        // missiles go for whatevers closest
+        for shot in self.shots.iter_mut() {
+            let target = if shot.homing {
+                self.enemies.iter()
+                    .map(|e| e.pos)
+                    .min_by(|a, b| a.dist(shot.pos).partial_cmp(&b.dist(shot.pos)).unwrap())
+            } else {
+                None
+            };
+            shot.update(dt, target);
+        }
+        let keep_rect = self.screen_rect.dilate(0.1);
+        let enemies = &mut self.enemies;
+        let archetypes = &self.archetypes;
+        let audio_events = &mut self.audio_events;
+        let t = self.t;
+        self.shots.retain(|shot| {
+            let r = shot.pos.rect_centered(2.0 * SHOT_R, 2.0 * SHOT_R);
+            if let Some(enemy) = enemies.iter_mut().find(|e| e.health > 0.0 && archetypes[e.kind].hit_by(e.pos, r)) {
+                if enemy.damage(&archetypes[enemy.kind], shot.damage, shot.owner, t) {
+                    audio_events.push(SOUND_PLAY | LASER_POP);
+                }
+                return false;
+            }
+            keep_rect.contains(shot.pos)
+        });
+
+        // bombs hit everything on screen and wipe out the bullets
+        for (i, damage) in bombs.iter() {
+            for enemy in self.enemies.iter_mut() {
+                if enemy.rect(&self.archetypes).overlaps(self.screen_rect).is_some() && enemy.damage(&self.archetypes[enemy.kind], *damage, *i, self.t) {
+                    self.audio_events.push(SOUND_PLAY | LASER_POP);
+                }
+            }
+            self.bullets.clear();
+            self.juice.flash(v4(1.0, 1.0, 1.0, 1.0), 0.15);
+            self.juice.add_trauma(0.7);
+            self.juice.hit_stop(0.05);
+        }
 
 
         ///////////////////////////////////////////
         // Enemy Death
         ///////////////////////////////////////////
-        let mut i = self.enemies_pos.len();
-        loop {
-            if i == 0 {
-                break;
-            }
-            i -= 1;
-
-            let et = self.enemies_type[i];
-            let er = self.enemies_pos[i].rect_centered(enemy_w[et], enemy_h[et]);
-
-            let death_by_laser = laser_rect.is_some() && laser_rect.unwrap().overlaps(er).is_some();
-
-            if er.right() < 0.0 || death_by_laser || pp_collected {
-                self.enemies_pos.swap_remove(i);
-                self.enemies_t_last_shoot.swap_remove(i);
-                self.enemies_v.swap_remove(i);
-                self.enemies_dob.swap_remove(i);
-                self.enemies_type.swap_remove(i);
-                self.score += 1;
-                if !pp_collected {
-                    outputs.audio_events.push(SOUND_PLAY | ENEMY_DIE);
+        let players = &mut self.players;
+        let audio_events = &mut self.audio_events;
+        let archetypes = &self.archetypes;
+        let stage = &mut self.stage;
+        let popups = &mut self.popups;
+        let particles = &mut self.particles;
+        let juice = &mut self.juice;
+        self.enemies.retain(|enemy| {
+            let er = enemy.rect(archetypes);
+
+            let killed = enemy.health <= 0.0;
+            let death_by_powerup = pp_cleared.is_some() && !enemy.boss;
+
+            if er.right() < 0.0 || killed || death_by_powerup {
+                // whoever killed it gets the points, one getting away breaks everyones combo
+                let killer = if killed { Some(enemy.last_hit_by) } else { pp_cleared };
+                match killer {
+                    Some(i) => {
+                        let player = &mut players[i];
+                        player.combo += 1;
+                        player.t_last_kill = t;
+                        let points = archetypes[enemy.kind].points * player.combo_multiplier() * player.effects.multiplier();
+                        player.score += points;
+                        popups.push(ScorePopup { pos: enemy.pos, points, owner: i, t });
+                    },
+                    None => {
+                        for player in players.iter_mut() {
+                            player.combo = 0;
+                        }
+                    },
+                }
+                if killer.is_some() {
+                    let burst = ENEMY_DEATH.with_colour(archetypes[enemy.kind].colour);
+                    particles.emit(&if enemy.boss { burst.with_count(burst.count * 8) } else { burst }, enemy.pos, 0.0);
+                    if enemy.boss {
+                        juice.add_trauma(1.0);
+                        juice.hit_stop(0.25);
+                        juice.flash(v4(1.0, 1.0, 1.0, 0.6), 0.3);
+                    } else {
+                        juice.add_trauma(0.15);
+                    }
+                }
+                if enemy.boss {
+                    if let Some(run) = stage.as_mut() {
+                        run.boss_killed = true;
+                    }
                 }
+                if !death_by_powerup {
+                    audio_events.push(SOUND_PLAY | ENEMY_DIE);
+                }
+                return false;
+            }
+            true
+        });
+
+
+        self.popups.retain(|p| t - p.t < POPUP_TIME);
+
+
+        ////////////////////////////
+        // Stage Clear
+        ////////////////////////////
+        if let Some(run) = self.stage.as_mut() {
+            let cleared = match run.stage.clear {
+                Clear::All => run.next_event == run.stage.events.len() && self.enemies.is_empty(),
+                Clear::Time(t) => self.t >= t,
+                Clear::Boss => run.boss_killed,
+            };
+            if cleared && run.t_cleared.is_none() && self.players.iter().any(|p| p.alive) {
+                run.t_cleared = Some(self.t);
+                self.enemies.clear();
+                self.bullets.clear();
+                self.audio_events.push(SOUND_PLAY | POWERUP);
             }
         }
 
@@ -338,13 +915,22 @@ impl Demo for Game {
         ////////////////////////////
         // Shooting
         ////////////////////////////
-        for i in 0..self.enemies_pos.len() {
-            let et = self.enemies_type[i];
-
-            if self.t - self.enemies_t_last_shoot[i] > enemy_shoot_interval[et] {
-                self.bullets_pos.push(self.enemies_pos[i]);
-                self.enemies_t_last_shoot[i] = self.t;
-                outputs.audio_events.push(SOUND_PLAY | ENEMY_SHOOT);
+        for enemy in self.enemies.iter_mut() {
+            let pattern = match self.archetypes[enemy.kind].fire(enemy.phase) {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            if self.t - enemy.t_last_shoot > pattern.interval {
+                // aims at whoevers closest
+                let target = self.players.iter()
+                    .filter(|p| p.in_play())
+                    .map(|p| p.pos)
+                    .min_by(|a, b| a.dist(enemy.pos).partial_cmp(&b.dist(enemy.pos)).unwrap())
+                    .unwrap_or(Vec2::new(0.0, enemy.pos.y));
+                self.bullets.extend(pattern.shoot(enemy.pos, target, enemy.shots));
+                enemy.shots += 1;
+                enemy.t_last_shoot = self.t;
+                self.audio_events.push(SOUND_PLAY | ENEMY_SHOOT);
             }
         }
 
@@ -352,56 +938,246 @@ impl Demo for Game {
         ///////////////////////////////
         // Bullets
         ///////////////////////////////
-        for bullet_pos in self.bullets_pos.iter_mut() {
-            bullet_pos.x -= dt * bullet_speed;
+        for bullet in self.bullets.iter_mut() {
+            bullet.update(dt);
+        }
+        // bullets go straight through anyone invulnerable
+        let hit_tris: Vec<Option<Triangle>> = self.players.iter()
+            .map(|p| if p.vulnerable(self.t) { Some(player_tri(p.pos).dilate(BULLET_H)) } else { None })
+            .collect();
+        let keep_rect = self.screen_rect.dilate(1.0);
+        self.bullets.retain(|b| {
+            if let Some(i) = hit_tris.iter().position(|tri| tri.map(|tri| tri.contains(b.pos)).unwrap_or(false)) {
+                hits.push(i);
+                return false;
+            }
+            keep_rect.contains(b.pos)
+        });
+        for i in hits.drain(..) {
+            self.hit_player(i);
+        }
+    }
+
+    // only reads the game, alpha is how far we are between the last update and the next
+    pub fn draw(&self, alpha: f32, outputs: &mut FrameOutputs) {
+        let player_border_colour = v4(0.0, 0.0, 0.0, 1.0);
+        let laser_colour = v4(1.0, 0.0, 0.0, 1.0);
+        let bullet_colour = self.settings.palette.bullet();
+        let blinking = self.settings.blink();
+
+        // everything gets drawn where it was partway through the last step,
+        // ie stepped back along its velocity by the time we havent got to yet
+        let back = (1.0 - alpha) * self.dt_last;
+        let t = self.t - back;
+        // shake on top of wherever its been put
+        let offset = outputs.canvas.offset;
+        outputs.canvas.offset = offset + self.juice.shake();
+
+
+        /////////////////////////////////////////////
+        // Player
+        /////////////////////////////////////////////
+        for (i, player) in self.players.iter().enumerate() {
+            let mut player_inner_colour = Player::colour(i);
+            let player_pos = player.pos - back * player.v;
+            let ptri = player_tri(player_pos);
+            let ptri_inner = ptri.dilate(-0.3);
+            let blink = blinking && t < player.invuln_until && (t * 20.0) as i32 % 2 == 0;
+            // if it cant blink it goes dim instead
+            if !blinking && t < player.invuln_until {
+                player_inner_colour = player_inner_colour.lerp(Vec4::grey(0.3), 0.6);
+            }
+            if player.in_play() && !blink {
+                outputs.canvas.put_triangle_struct(ptri, 1.6, player_border_colour);
+                outputs.canvas.put_triangle_struct(ptri_inner, 1.7, player_inner_colour);
+            }
+            // a ring per shield, flickers when its about to run out
+            let shield = powerup_type(PowerupKind::Shield);
+            let shield_left = player.effects.remaining(PowerupKind::Shield, t);
+            if player.in_play() && !(blinking && shield_left < 2.0 && (t * 10.0) as i32 % 2 == 0) {
+                for j in 0..player.effects.count(PowerupKind::Shield) {
+                    let r = 0.03 + 0.008 * j as f32;
+                    outputs.canvas.put_circle(player_pos, r, 1.55 - 0.001 * j as f32, if j % 2 == 0 { shield.colour } else { v4(0.0, 0.0, 0.0, 1.0) });
+                }
+            }
+            // blowing up, bits fly off and shrink. time stops once everyones out so keep going on t_dead
+            let since_died = t - player.t_died + if self.alive() { 0.0 } else { self.t_dead };
+            if since_died < RESPAWN_TIME {
+                let k = since_died / RESPAWN_TIME;
+                outputs.canvas.put_circle(player_pos, 0.04 * (1.0 - k), 1.65, v4(1.0, 0.6, 0.0, 1.0));
+                for j in 0..8 {
+                    let theta = j as f32 * TAU / 8.0 + 0.3;
+                    let p = player_pos + (0.15 * k) * v2(theta.cos(), theta.sin());
+                    let r = 0.012 * (1.0 - k);
+                    let spin = theta + 10.0 * k;
+                    outputs.canvas.put_triangle(p + r * v2(spin.cos(), spin.sin()), p + r * v2((spin + TAU/3.0).cos(), (spin + TAU/3.0).sin()), p + r * v2((spin + 2.0*TAU/3.0).cos(), (spin + 2.0*TAU/3.0).sin()), 1.66, if j % 2 == 0 { player_inner_colour } else { v4(1.0, 1.0, 0.0, 1.0) });
+                }
+            }
+
+
+            ///////////////////////////////////
+            // Weapons
+            ///////////////////////////////////
+            if let Some(beam) = player.arsenal.beam_at(self.t) {
+                let colour = if beam.kind == WeaponKind::Laser { laser_colour } else { v4(0.6, 1.0, 1.0, 1.0) };
+                let mut r = beam.rect(player_pos);
+                if let Some(hit) = player.laser_hit {
+                    r.w = (hit.x - player_pos.x).max(0.0);
+                }
+                outputs.canvas.put_rect(r, 1.5, colour);
+            }
+            let charge = player.arsenal.weapon();
+            if player.in_play() && charge.kind == WeaponKind::Beam && charge.meter > 0.0 {
+                outputs.canvas.put_circle(player_pos + v2(PLAYER_W/2.0, 0.0), 0.005 + 0.015 * charge.meter, 1.75, v4(0.6, 1.0, 1.0, 1.0));
+            }
         }
-        for bullet_pos in self.bullets_pos.iter() {
-            outputs.canvas.put_rect(bullet_pos.rect_centered(bullet_w, bullet_h), 1.8, bullet_colour);
+        for shot in self.shots.iter() {
+            let p = shot.pos - back * shot.v;
+            let colour = Player::colour(shot.owner);
+            if shot.homing {
+                let half = 0.0125 * shot.v.normalize();
+                outputs.canvas.put_line(p - half, p + half, SHOT_R, 1.75, v4(1.0, 0.5, 0.0, 1.0));
+                outputs.canvas.put_circle(p + half, SHOT_R/2.0, 1.76, colour);
+            } else {
+                outputs.canvas.put_circle(p, SHOT_R, 1.75, colour);
+            }
         }
-        for bullet_pos in self.bullets_pos.iter() {
-            if ptri.dilate(bullet_h).contains(*bullet_pos) {
-                self.player_alive = false;
-                outputs.audio_events.push(SOUND_PLAY | PLAYER_DIE);
+
+
+        /////////////////////////////////////////
+        // Powerups
+        /////////////////////////////////////////
+        if let Some(powerup) = self.powerup.as_ref() {
+            let ppos = powerup.pos + Vec2::new(POWERUP_SPEED * back, 0.0);
+            let pt = powerup_type(powerup.kind);
+            let r = 0.03;
+            outputs.canvas.put_triangle(
+                r * Vec2::new((t * TAU/3.0).sin(), (t * TAU/3.0).cos()) + ppos, 
+                r * Vec2::new((t * TAU/3.0 + TAU/3.0).sin(), (t * TAU/3.0  + TAU/3.0).cos()) + ppos, 
+                r * Vec2::new((t * TAU/3.0 + 2.0 * TAU/3.0).sin(), (t * TAU/3.0  + 2.0 * TAU/3.0).cos()) + ppos, 
+            3.0, pt.colour);
+            outputs.canvas.put_triangle(
+                r * Vec2::new((-t * TAU/3.0).sin(), (-t * TAU/3.0).cos()) + ppos, 
+                r * Vec2::new((-t * TAU/3.0 + TAU/3.0).sin(), (-t * TAU/3.0  + TAU/3.0).cos()) + ppos, 
+                r * Vec2::new((-t * TAU/3.0 + 2.0 * TAU/3.0).sin(), (-t * TAU/3.0  + 2.0 * TAU/3.0).cos()) + ppos, 
+            2.9, Vec4::new(1.0, 1.0, 1.0, 1.0));
+            outputs.glyphs.push_center_str(pt.icon, ppos.x, ppos.y, 0.02, 0.02, 3.1, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        }
+
+        self.juice.draw_flashes(&mut outputs.canvas, self.screen_rect, 1.4);
+
+
+        /////////////////////////////////////////////////
+        // Enemies
+        /////////////////////////////////////////////////
+        for enemy in self.enemies.iter() {
+            let p = enemy.pos - back * enemy.v;
+            let flash = self.t - enemy.t_hit < 0.1 && (!blinking || (t * 30.0) as i32 % 2 == 0);
+            self.archetypes[enemy.kind].draw(&mut outputs.canvas, p, t, flash, self.settings.palette);
+        }
+
+
+        ///////////////////////////////
+        // Bullets
+        ///////////////////////////////
+        for bullet in self.bullets.iter() {
+            let p = bullet.pos - back * bullet.v;
+            let half = BULLET_W/2.0 * bullet.v.normalize();
+            outputs.canvas.put_line(p - half, p + half, BULLET_H, 1.8, bullet_colour);
+            if self.settings.outlines {
+                let edge = 0.4 * BULLET_H * bullet.v.normalize();
+                outputs.canvas.put_line(p - half - edge, p + half + edge, BULLET_H * 1.8, 1.79, v4(0.0, 0.0, 0.0, 1.0));
             }
         }
-        self.bullets_pos.retain(|x| !ptri.dilate(bullet_h).contains(*x) && x.x > -1.0);
+        self.particles.draw(&mut outputs.canvas, back, 1.78);
+        for popup in self.popups.iter() {
+            let k = ((t - popup.t) / POPUP_TIME).clamp(0.0, 1.0);
+            let size = 0.025 * (1.0 - 0.5 * k);
+            let colour = if self.players.len() == 1 { Vec4::new(1.0, 1.0, 1.0, 1.0) } else { Player::colour(popup.owner) };
+            outputs.glyphs.push_center_str(&popup.points.to_string(), popup.pos.x, popup.pos.y - 0.06 * k, size, size, 2.08, colour);
+        }
+        outputs.canvas.offset = offset;
         
 
         /////////////////////////////////////
         // Interface
         /////////////////////////////////////
-        outputs.glyphs.push_str(format!("score: {}", self.score).as_str(), 0.02, 0.02, 0.03, 0.03, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
-        if self.player_alive {
+        for (i, player) in self.players.iter().enumerate() {
+            let y = 0.02 + i as f32 * 0.04;
+            let (score_str, colour) = if self.players.len() == 1 {
+                (format!("score: {}", player.score), Vec4::new(1.0, 1.0, 1.0, 1.0))
+            } else {
+                (format!("p{}: {}", i + 1, player.score), Player::colour(i))
+            };
+            outputs.glyphs.push_str(&score_str, 0.02, y, 0.03, 0.03, 2.1, colour);
+            let x = self.draw_lives(i, 0.02 + (score_str.len() + 1) as f32 * 0.03, y + 0.015, outputs);
+            let x = self.draw_weapon(i, x + 0.02, y + 0.015, outputs);
+            self.draw_effects(i, x + 0.02, y + 0.015, outputs);
+        }
+        if let Some(boss) = self.enemies.iter().find(|e| e.boss) {
+            self.draw_boss_bar(boss, outputs);
+        }
+        if let Some(run) = self.stage.as_ref() {
+            let x = self.screen_rect.w/2.0;
+            if self.t < run.checkpoint_t() + 3.0 {
+                outputs.glyphs.push_center_str(&run.stage.name, x, self.screen_rect.h * 0.15, 0.05, 0.05, 2.1, Vec4::new(1.0, 1.0, 0.0, 1.0));
+            }
+            if self.t - run.t_checkpoint < 2.0 {
+                outputs.glyphs.push_center_str("checkpoint", x, self.screen_rect.h * 0.15, 0.04, 0.04, 2.1, Vec4::new(0.0, 1.0, 1.0, 1.0));
+            }
+        }
+        if let Some(t_cleared) = self.stage.as_ref().and_then(|run| run.t_cleared) {
+            let banner_rect = self.screen_rect.child(0.0, 0.33, 1.0, 0.25);
+            outputs.canvas.put_rect(banner_rect, 1.9, Vec4::new(0.0, 0.0, 0.0, 1.0));
+            outputs.canvas.put_rect(banner_rect.dilate_pc(0.01), 1.85, Vec4::new(0.0, 1.0, 0.0, 1.0));
+
+            let x = self.screen_rect.w/2.0;
+            let mut y = self.screen_rect.h * 0.37;
+            outputs.glyphs.push_center_str("stage clear", x, y, 0.08, 0.08, 2.1, Vec4::new(0.0, 1.0, 0.0, 1.0));
+            y += 0.1;
+            self.draw_banner_scores(y, outputs);
+            y = 0.66;
+            if (self.t - t_cleared) % 2.0 > 1.0 || !blinking {
+                let again = format!("press {} to play again", self.settings.bindings[0].label(Action::Restart));
+                outputs.glyphs.push_center_str(&again, x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+            }
+        } else if self.alive() {
 
             if self.t < 3.0 {
-                let x = inputs.screen_rect.w/2.0;
-                let mut y = inputs.screen_rect.h * 0.3;
-                outputs.glyphs.push_center_str("wasd - move", x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
-                let mut y = inputs.screen_rect.h * 0.6;
-                outputs.glyphs.push_center_str("space - shoot", x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+                // whatever p1 has them set to
+                let keys: Vec<String> = ACTIONS[..6].iter().map(|a| self.settings.bindings[0].label(*a)).collect();
+                let x = self.screen_rect.w/2.0;
+                let y = self.screen_rect.h * 0.3;
+                outputs.glyphs.push_center_str(&format!("{} - move", keys[..4].join(" ")), x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+                let y = self.screen_rect.h * 0.6;
+                outputs.glyphs.push_center_str(&format!("{} - shoot", keys[4]), x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+                let y = self.screen_rect.h * 0.66;
+                outputs.glyphs.push_center_str(&format!("{} - switch weapon", keys[5]), x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
                 
             }
         } else {
-            let banner_rect = inputs.screen_rect.child(0.0, 0.33, 1.0, 0.25);
+            let banner_rect = self.screen_rect.child(0.0, 0.33, 1.0, 0.25);
             outputs.canvas.put_rect(banner_rect, 1.9, Vec4::new(0.0, 0.0, 0.0, 1.0));
             outputs.canvas.put_rect(banner_rect.dilate_pc(0.01), 1.85, Vec4::new(1.0, 0.0, 0.0, 1.0));
 
-            let x = inputs.screen_rect.w/2.0;
-            let mut y = inputs.screen_rect.h * 0.37;
+            let x = self.screen_rect.w/2.0;
+            let mut y = self.screen_rect.h * 0.37;
             outputs.glyphs.push_center_str("you died", x, y, 0.08, 0.08, 2.1, Vec4::new(1.0, 0.0, 0.0, 1.0));
             y += 0.1;
-            outputs.glyphs.push_center_str(format!("score: {}", self.score).as_str(), x, y, 0.08, 0.08, 2.1, Vec4::new(1.0, 1.0, 0.0, 1.0));
+            self.draw_banner_scores(y, outputs);
             y = 0.66;
-            if inputs.t % 2.0 > 1.0 {
-                outputs.glyphs.push_center_str("press r to play again", x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+            if self.t_dead % 2.0 > 1.0 || !blinking {
+                let key = self.settings.bindings[0].label(Action::Restart);
+                let again = if self.stage.as_ref().map(|run| run.checkpoints_reached > 0).unwrap_or(false) { format!("press {} to go from checkpoint", key) } else { format!("press {} to play again", key) };
+                outputs.glyphs.push_center_str(&again, x, y, 0.04, 0.04, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
             }
         }
 
         
-        let tb = background(self.t, self.seed, 400, (inputs.screen_rect.aspect() * 400.0) as usize);
+        let tb = background(t, self.seed, 400, (self.screen_rect.aspect() * 400.0) as usize);
         // let tb = background(self.t, self.seed, 400, 400);
         outputs.set_texture.push((tb, 0));
-        outputs.draw_texture.push((inputs.screen_rect, 0));
+        outputs.draw_texture.push((self.screen_rect, 0));
 
         
 
@@ -410,6 +1186,147 @@ impl Demo for Game {
     }
 }
 
+impl Game {
+    // a little ship per life left then a pip per health, if theres more than one
+    // returns where it got up to
+    fn draw_lives(&self, i: usize, mut x: f32, y: f32, outputs: &mut FrameOutputs) -> f32 {
+        let player = &self.players[i];
+        for _ in 0..player.lives {
+            let tri = Triangle::new(v2(x, y - 0.01), v2(x + 0.025, y), v2(x, y + 0.01));
+            outputs.canvas.put_triangle_struct(tri, 2.05, Player::colour(i));
+            x += 0.03;
+        }
+        if self.rules.health > 1 {
+            x += 0.01;
+            for i in 0..self.rules.health {
+                let colour = if i < player.health { v4(1.0, 0.1, 0.1, 1.0) } else { v4(0.3, 0.3, 0.3, 1.0) };
+                outputs.canvas.put_rect(Rect::new(x, y - 0.008, 0.01, 0.016), 2.05, colour);
+                x += 0.014;
+            }
+        }
+        x
+    }
+
+    // name, level pips and a gauge for heat, charge or ammo
+    fn draw_weapon(&self, i: usize, mut x: f32, y: f32, outputs: &mut FrameOutputs) -> f32 {
+        let w = self.players[i].arsenal.weapon();
+        outputs.glyphs.push_str(w.kind.name(), x, y - 0.01, 0.02, 0.02, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+        x += (w.kind.name().len() as f32 + 0.5) * 0.02;
+        for l in 0..MAX_LEVEL {
+            let colour = if l < w.level { v4(1.0, 1.0, 0.0, 1.0) } else { v4(0.3, 0.3, 0.3, 1.0) };
+            outputs.canvas.put_rect(Rect::new(x, y - 0.005, 0.008, 0.01), 2.05, colour);
+            x += 0.012;
+        }
+        x += 0.01;
+        let bar = Rect::new(x, y - 0.006, 0.1, 0.012);
+        let fill = match w.kind {
+            WeaponKind::Laser | WeaponKind::Spread if w.cooling => v4(1.0, 0.0, 0.0, 1.0),
+            WeaponKind::Laser | WeaponKind::Spread => v4(1.0, 0.5, 0.0, 1.0),
+            WeaponKind::Beam => v4(0.6, 1.0, 1.0, 1.0),
+            WeaponKind::Missiles | WeaponKind::Bombs => v4(1.0, 1.0, 1.0, 1.0),
+        };
+        outputs.canvas.put_rect(bar.dilate(0.002), 2.0, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        outputs.canvas.put_rect(Rect::new(bar.x, bar.y, bar.w * w.gauge().clamp(0.0, 1.0), bar.h), 2.02, fill);
+        // a notch per round of ammo
+        for j in 1..w.ammo_max() as u32 {
+            let nx = bar.x + bar.w * j as f32 / w.ammo_max();
+            outputs.canvas.put_rect(Rect::new(nx - 0.001, bar.y, 0.002, bar.h), 2.04, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        }
+        bar.right()
+    }
+
+    // combo multiplier then an icon for each effect going, how many are stacked and a bar for how long is left
+    fn draw_effects(&self, i: usize, mut x: f32, y: f32, outputs: &mut FrameOutputs) {
+        let player = &self.players[i];
+        if player.combo_multiplier() > 1 {
+            let label = format!("x{}", player.combo_multiplier());
+            let colour = v4(1.0, 0.6, 0.0, 1.0);
+            outputs.glyphs.push_str(&label, x, y - 0.01, 0.02, 0.02, 2.1, colour);
+            let left = 1.0 - (self.t - player.t_last_kill) / COMBO_WINDOW;
+            outputs.canvas.put_rect(Rect::new(x, y + 0.012, 0.04 * left.max(0.0), 0.004), 2.05, colour);
+            x += 0.02 * label.len() as f32 + 0.03;
+        }
+        let effects = &player.effects;
+        for pt in POWERUP_TYPES.iter().filter(|pt| effects.has(pt.kind)) {
+            let n = effects.count(pt.kind);
+            let label = if n > 1 { format!("{}{}", pt.icon, n) } else { pt.icon.to_owned() };
+            outputs.glyphs.push_str(&label, x, y - 0.01, 0.02, 0.02, 2.1, pt.colour);
+            let left = effects.remaining(pt.kind, self.t) / pt.duration;
+            outputs.canvas.put_rect(Rect::new(x, y + 0.012, 0.04 * left, 0.004), 2.05, pt.colour);
+            x += 0.02 * label.len() as f32 + 0.03;
+        }
+    }
+
+    // everyones score, spread across the banner
+    fn draw_banner_scores(&self, y: f32, outputs: &mut FrameOutputs) {
+        let n = self.players.len();
+        if n == 1 {
+            outputs.glyphs.push_center_str(format!("score: {}", self.players[0].score).as_str(), self.screen_rect.w/2.0, y, 0.08, 0.08, 2.1, Vec4::new(1.0, 1.0, 0.0, 1.0));
+            return;
+        }
+        for (i, player) in self.players.iter().enumerate() {
+            let x = self.screen_rect.w * (i + 1) as f32 / (n + 1) as f32;
+            outputs.glyphs.push_center_str(format!("p{}: {}", i + 1, player.score).as_str(), x, y + 0.015, 0.05, 0.05, 2.1, Player::colour(i));
+        }
+    }
+
+    fn draw_boss_bar(&self, boss: &Enemy, outputs: &mut FrameOutputs) {
+        let a = &self.archetypes[boss.kind];
+        if a.health <= 0.0 {
+            return;
+        }
+        let x = self.screen_rect.w/2.0;
+        outputs.glyphs.push_center_str(&a.name, x, 0.03, 0.025, 0.025, 2.1, Vec4::new(1.0, 1.0, 1.0, 1.0));
+
+        let bar = Rect::new(self.screen_rect.w * 0.25, 0.055, self.screen_rect.w * 0.5, 0.015);
+        let frac = (boss.health / a.health).max(0.0);
+        let fill = if self.t - boss.t_hit < 0.1 { Vec4::new(1.0, 1.0, 1.0, 1.0) } else { Vec4::new(1.0, 0.1, 0.1, 1.0) };
+        outputs.canvas.put_rect(bar.dilate(0.003), 2.0, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        outputs.canvas.put_rect(Rect::new(bar.x, bar.y, bar.w * frac, bar.h), 2.02, fill);
+        // notches where it changes phase
+        for phase in a.phases.iter() {
+            outputs.canvas.put_rect(Rect::new(bar.x + bar.w * phase.below - 0.002, bar.y, 0.004, bar.h), 2.04, Vec4::new(1.0, 1.0, 0.0, 1.0));
+        }
+    }
+}
+
+impl Demo for Game {
+    fn update(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let mut input = GameInput::from_frame(inputs, &self.settings.bindings);
+        input.restart |= std::mem::take(&mut self.restart_pending);
+        Game::update(self, &input);
+        outputs.audio_events.append(&mut self.audio_events);
+        outputs.hit_stop = outputs.hit_stop.max(self.take_hit_stop());
+    }
+
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        self.draw(inputs.alpha, outputs);
+    }
+
+    fn run_over(&self) -> Option<RunSummary> {
+        if self.alive() && !self.cleared() {
+            return None;
+        }
+        Some(RunSummary { score: self.score(), t: self.t, seed: self.seed })
+    }
+
+    fn apply_settings(&mut self, settings: &Settings) {
+        self.set_settings(*settings);
+    }
+
+    fn pausable(&self) -> bool {
+        true
+    }
+
+    fn restart(&mut self) {
+        self.restart_pending = true;
+    }
+
+    fn players(&self) -> usize {
+        self.rules.players as usize
+    }
+}
+
 fn background(t: f32, seed: u32, w: usize, h: usize) -> TextureBuffer {
 
     let mut tb = TextureBuffer::new(w, h);
@@ -477,4 +1394,212 @@ fn cloud_noise(x: f32, y: f32, seed: u32) -> f32 {
     // let n2 = noise2d(x/5.0, y/5.0, seed * 154171234);
     // n1 * n2
     n1
-}
\ No newline at end of file
+}
+#[cfg(test)]
+fn test_input(i: u32) -> GameInput {
+    // weave up and down shooting, fire in bursts so the laser cools off
+    GameInput {
+        dt: crate::fixed_step::SIM_DT,
+        screen_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+        players: [PlayerInput {
+            steer: Vec2::new(0.0, if (i / 120).is_multiple_of(2) { -0.5 } else { 0.5 }),
+            fire: !(i / 60).is_multiple_of(3),
+            switch: false,
+        }; MAX_PLAYERS],
+        restart: false,
+    }
+}
+
+#[test]
+fn test_update_without_drawing() {
+    let mut game = Game::new(1234);
+    for i in 0..10000 {
+        game.update(&test_input(i));
+    }
+    assert!(game.t > 0.0);
+}
+
+#[test]
+fn test_deterministic() {
+    let mut a = Game::new(98765);
+    let mut b = Game::new(98765);
+    for i in 0..10000 {
+        a.update(&test_input(i));
+        b.update(&test_input(i));
+        assert_eq!(a.audio_events, b.audio_events);
+    }
+    assert_eq!(a.t.to_bits(), b.t.to_bits());
+    assert_eq!(a.players, b.players);
+    assert_eq!(a.enemies, b.enemies);
+    assert_eq!(a.bullets, b.bullets);
+    assert_eq!(a.shots, b.shots);
+}
+
+#[test]
+fn test_boss_phases() {
+    let archetypes = parse_archetypes("enemy boss\nspeed 0\nspawn never\nhealth 0.8\nphase 0.5\nfire none\n").unwrap();
+    let mut game = Game::with_archetypes(1, archetypes);
+    game.spawn_enemy(0, v2(0.5, 0.5), None, true);
+    let input = GameInput {
+        dt: crate::fixed_step::SIM_DT,
+        screen_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+        players: [PlayerInput { steer: v2(0.0, 0.0), fire: true, switch: false }; MAX_PLAYERS],
+        restart: false,
+    };
+    for _ in 0..60 {
+        game.update(&input);
+    }
+    assert_eq!(game.enemies.len(), 1);
+    assert_eq!(game.enemies[0].phase, 1);
+    for _ in 0..60 {
+        game.update(&input);
+    }
+    assert!(game.enemies.is_empty());
+}
+
+#[test]
+fn test_lives() {
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspawn never\n").unwrap());
+    game.set_rules(Rules { lives: 2, ..Rules::default() });
+    let mut input = test_input(0);
+    input.players[0] = PlayerInput::IDLE;
+    let shoot_player = |game: &mut Game| game.bullets.push(Bullet { pos: game.players[0].pos, v: v2(-0.01, 0.0), accel: 0.0, curve: None, age: 0.0 });
+
+    shoot_player(&mut game);
+    game.update(&input);
+    assert_eq!(game.players[0].lives, 1);
+    assert!(game.alive() && !game.players[0].in_play());
+
+    // respawns invulnerable
+    for _ in 0..(120.0 * RESPAWN_TIME) as usize + 1 {
+        game.update(&input);
+    }
+    assert!(game.players[0].in_play());
+    shoot_player(&mut game);
+    game.update(&input);
+    assert_eq!(game.players[0].lives, 1);
+
+    for _ in 0..(120.0 * INVULN_TIME) as usize + 1 {
+        game.update(&input);
+    }
+    shoot_player(&mut game);
+    game.update(&input);
+    assert!(!game.alive());
+}
+
+#[test]
+fn test_coop_shared_lives() {
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspawn never\n").unwrap());
+    game.set_rules(Rules { lives: 2, health: 1, players: 2, shared_lives: true });
+    let mut input = test_input(0);
+    input.players = [PlayerInput::IDLE; MAX_PLAYERS];
+    let shoot = |game: &mut Game, i: usize| game.bullets.push(Bullet { pos: game.players[i].pos, v: v2(-0.01, 0.0), accel: 0.0, curve: None, age: 0.0 });
+
+    shoot(&mut game, 0);
+    game.update(&input);
+    assert_eq!(game.players[1].lives, 1);
+
+    // p2 uses up the last one, p1 keeps going on what it has
+    shoot(&mut game, 1);
+    game.update(&input);
+    assert!(!game.players[1].alive);
+    assert!(game.alive());
+
+    for _ in 0..(120.0 * (RESPAWN_TIME + INVULN_TIME)) as usize + 2 {
+        game.update(&input);
+    }
+    shoot(&mut game, 0);
+    game.update(&input);
+    assert!(!game.alive());
+    assert_eq!(game.scores().len(), 2);
+}
+
+#[test]
+fn test_shield() {
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspawn never\n").unwrap());
+    let mut input = test_input(0);
+    input.players[0] = PlayerInput::IDLE;
+    game.players[0].effects.add(PowerupKind::Shield, 0.0);
+    game.bullets.push(Bullet { pos: game.players[0].pos, v: v2(-0.01, 0.0), accel: 0.0, curve: None, age: 0.0 });
+    game.update(&input);
+    assert!(game.players[0].in_play());
+    assert_eq!(game.players[0].lives, 3);
+    assert!(!game.players[0].effects.has(PowerupKind::Shield));
+
+    // runs out on its own too
+    game.players[0].effects.add(PowerupKind::Shield, game.t);
+    for _ in 0..(120.0 * powerup_type(PowerupKind::Shield).duration) as usize + 1 {
+        game.update(&input);
+    }
+    assert!(!game.players[0].effects.has(PowerupKind::Shield));
+}
+
+#[test]
+fn test_laser_stops_at_first() {
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspeed 0\nspawn never\nhealth 10\n").unwrap());
+    let y = game.players[0].pos.y;
+    game.spawn_enemy(0, v2(0.5, y), None, false);
+    game.spawn_enemy(0, v2(0.8, y), None, false);
+    let mut input = test_input(0);
+    input.players[0] = PlayerInput { steer: v2(0.0, 0.0), fire: true, switch: false };
+    for _ in 0..60 {
+        game.update(&input);
+    }
+    assert!(game.enemies[0].health < 10.0);
+    assert_eq!(game.enemies[1].health, 10.0);
+    assert!((game.players[0].laser_hit.unwrap().x - 0.475).abs() < 0.001);
+
+    // piercing goes through both
+    game.players[0].effects.add(PowerupKind::Pierce, game.t);
+    game.update(&input);
+    assert!(game.enemies[1].health < 10.0);
+}
+
+#[test]
+fn test_exact_kill() {
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspeed 0\nspawn never\nhealth 1\n").unwrap());
+    game.spawn_enemy(0, v2(0.5, 0.5), None, false);
+    game.shots.push(Shot { pos: v2(0.5, 0.5), v: v2(0.0, 0.0), damage: 1.0, homing: false, owner: 0 });
+    game.update(&test_input(0));
+    assert!(game.enemies.is_empty());
+
+    // and nothing at all is enough for health 0
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspeed 0\nspawn never\n").unwrap());
+    game.spawn_enemy(0, v2(0.5, 0.5), None, false);
+    game.update(&test_input(0));
+    assert_eq!(game.enemies.len(), 1);
+    game.shots.push(Shot { pos: v2(0.5, 0.5), v: v2(0.0, 0.0), damage: 0.01, homing: false, owner: 0 });
+    game.update(&test_input(0));
+    assert!(game.enemies.is_empty());
+}
+
+#[test]
+fn test_combo() {
+    let mut game = Game::with_archetypes(1, parse_archetypes("enemy rock\nspeed 0\nspawn never\npoints 10\n").unwrap());
+    let input = test_input(0);
+    for i in 0..5 {
+        game.spawn_enemy(0, v2(0.5, 0.1 * i as f32 + 0.1), None, false);
+    }
+    for enemy in game.enemies.iter_mut() {
+        enemy.health = -1.0;
+    }
+    game.update(&input);
+    // the fifth one in the chain is worth double
+    assert_eq!(game.players[0].score, 60);
+    assert_eq!(game.players[0].combo_multiplier(), 2);
+    assert_eq!(game.popups.len(), 5);
+
+    // one getting away is worth nothing and breaks the chain
+    game.spawn_enemy(0, v2(-0.5, 0.5), None, false);
+    game.update(&input);
+    assert_eq!(game.players[0].score, 60);
+    assert_eq!(game.players[0].combo, 0);
+
+    // so does waiting too long
+    game.players[0].combo = 3;
+    for _ in 0..(120.0 * COMBO_WINDOW) as usize + 1 {
+        game.update(&input);
+    }
+    assert_eq!(game.players[0].combo, 0);
+    assert!(game.popups.is_empty());
+}
@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use glutin::event::VirtualKeyCode;
+
+use crate::fixed_step::*;
+use crate::game::*;
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+use crate::replay::*;
+use crate::stage::*;
+
+// Runs the game with no window, GL context or audio device. The keys come from a
+// script instead of the keyboard and whatever gets drawn is thrown away.
+
+const FRAME_DT: f32 = 1.0 / 60.0;
+
+// lines of `<time> <keys held from then on>`, eg
+//
+// # weave and shoot
+// 0    W Space
+// 1.5  S Space
+// 3
+pub struct InputScript {
+    entries: Vec<(f32, HashSet<VirtualKeyCode>)>,
+}
+
+impl InputScript {
+    pub fn new() -> InputScript {
+        InputScript { entries: Vec::new() }
+    }
+
+    pub fn parse(s: &str) -> Result<InputScript, anyhow::Error> {
+        let mut entries: Vec<(f32, HashSet<VirtualKeyCode>)> = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let mut words = line.split_whitespace();
+            let t = match words.next() {
+                Some(w) => w.parse::<f32>().map_err(|_| anyhow::Error::msg(format!("line {}: bad time '{}'", i + 1, w)))?,
+                None => continue,
+            };
+            if entries.last().map(|e| e.0 > t).unwrap_or(false) {
+                return Err(anyhow::Error::msg(format!("line {}: times have to go in order", i + 1)));
+            }
+            let mut keys = HashSet::new();
+            for w in words {
+                keys.insert(key_from_name(w).ok_or_else(|| anyhow::Error::msg(format!("line {}: unknown key '{}'", i + 1, w)))?);
+            }
+            entries.push((t, keys));
+        }
+        Ok(InputScript { entries })
+    }
+
+    pub fn load(path: &str) -> Result<InputScript, anyhow::Error> {
+        InputScript::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn keys_at(&self, t: f32) -> HashSet<VirtualKeyCode> {
+        self.entries.iter().rev().find(|e| e.0 <= t).map(|e| e.1.clone()).unwrap_or_default()
+    }
+}
+
+pub struct HeadlessResult {
+    pub score: i32,
+    pub scores: Vec<i32>,
+    pub t: f32,
+    pub alive: bool,
+    pub cleared: bool,
+}
+
+// plays until the player dies, the stage gets cleared or max_t seconds of game time go by
+pub fn run_headless(mut game: Game, max_t: f32, script: &InputScript) -> HeadlessResult {
+    let mut stepper = FixedStep::new();
+    let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), game.seed());
+    inputs.dt = FRAME_DT;
+
+    while game.alive() && !game.cleared() && game.t() < max_t {
+        inputs.prev_keys = std::mem::replace(&mut inputs.curr_keys, script.keys_at(inputs.t));
+        let mut outputs = FrameOutputs::new(inputs.screen_rect.aspect());
+        stepper.update(&mut game, &inputs, &mut outputs);
+        inputs.t += FRAME_DT;
+        inputs.frame += 1;
+    }
+
+    HeadlessResult {
+        score: game.score(),
+        scores: game.scores(),
+        t: game.t(),
+        alive: game.alive(),
+        cleared: game.cleared(),
+    }
+}
+
+// ssss --headless [--seed N] [--time SECONDS] [--script FILE] [--stage FILE] [--lives N] [--health N] [--hardcore] [--players N] [--shared-lives]
+// ssss --headless --replay FILE
+pub fn headless_main(args: &[String]) {
+    if let Some(path) = crate::arg_value(args, "--replay") {
+        let replay = Replay::load(path).unwrap_or_else(|e| panic!("couldnt load replay {}: {}", path, e));
+        let game = replay.simulate();
+        println!("seed: {}", replay.seed);
+        println!("score: {}", game.score());
+        println!("survived: {:.3}s", game.t());
+        println!("{}", check_message(&replay, &game));
+        if game.score() != replay.final_score {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let seed = crate::arg_value(args, "--seed").map(|s| s.parse().expect("bad --seed")).unwrap_or(1);
+    let max_t = crate::arg_value(args, "--time").map(|s| s.parse().expect("bad --time")).unwrap_or(600.0);
+    let script = match crate::arg_value(args, "--script") {
+        Some(path) => InputScript::load(path).unwrap_or_else(|e| panic!("couldnt load script {}: {}", path, e)),
+        None => InputScript::new(),
+    };
+
+    let mut game = match crate::arg_value(args, "--stage") {
+        Some(path) => Game::with_stage(seed, Stage::load(path).unwrap_or_else(|e| panic!("couldnt load stage {}: {}", path, e))),
+        None => Game::new(seed),
+    };
+    game.set_rules(Rules::from_args(args));
+
+    let result = run_headless(game, max_t, &script);
+    println!("seed: {}", seed);
+    println!("score: {}", result.score);
+    if result.scores.len() > 1 {
+        for (i, score) in result.scores.iter().enumerate() {
+            println!("  p{}: {}", i + 1, score);
+        }
+    }
+    let how = if result.cleared { " (stage clear)" } else if result.alive { " (still alive)" } else { "" };
+    println!("survived: {:.3}s{}", result.t, how);
+}
+
+#[test]
+fn test_headless_script() {
+    let script = InputScript::parse("# up and shoot then down and shoot\n0 W Space\n1.0 S Space\n2.0 space\n").unwrap();
+    assert!(script.keys_at(0.5).contains(&VirtualKeyCode::W));
+    assert!(script.keys_at(1.5).contains(&VirtualKeyCode::S));
+    assert!(!script.keys_at(2.5).contains(&VirtualKeyCode::S));
+    assert!(InputScript::parse("0 Nope").is_err());
+
+    let a = run_headless(Game::new(4242), 30.0, &script);
+    let b = run_headless(Game::new(4242), 30.0, &script);
+    assert_eq!(a.score, b.score);
+    assert_eq!(a.t.to_bits(), b.t.to_bits());
+}
@@ -0,0 +1,270 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use glutin::event::VirtualKeyCode;
+
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+
+// The top 10, kept in a text file in the users data directory. One line each:
+//
+// <score> <seconds survived> <yyyy-mm-dd> <seed> <name>
+//
+// It gets written to a temp file next to it and renamed over the top so a crash
+// halfway through leaves the old table there instead of half a new one.
+
+pub const MAX_SCORES: usize = 10;
+pub const MAX_NAME: usize = 12;
+const FILE_NAME: &str = "highscores.txt";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub score: i32,
+    pub t: f32,
+    pub date: String,
+    pub seed: u32,
+}
+
+// how a run ended up, for seeing if it makes the table
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunSummary {
+    pub score: i32,
+    pub t: f32,
+    pub seed: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    // $SSSS_DATA_DIR if thats set, otherwise wherever the os keeps app data
+    pub fn dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os("SSSS_DATA_DIR") {
+            return PathBuf::from(dir);
+        }
+        let env = |k: &str| std::env::var_os(k).map(PathBuf::from);
+        let base = if cfg!(windows) {
+            env("APPDATA")
+        } else if cfg!(target_os = "macos") {
+            env("HOME").map(|h| h.join("Library").join("Application Support"))
+        } else {
+            env("XDG_DATA_HOME").or_else(|| env("HOME").map(|h| h.join(".local").join("share")))
+        };
+        base.unwrap_or_else(|| PathBuf::from(".")).join("ssss")
+    }
+
+    pub fn path() -> PathBuf {
+        HighScores::dir().join(FILE_NAME)
+    }
+
+    pub fn parse(s: &str) -> Result<HighScores, anyhow::Error> {
+        let mut entries = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let err = |what: &str| anyhow::Error::msg(format!("line {}: {}", i + 1, what));
+            let words: Vec<&str> = line.splitn(5, ' ').collect();
+            if words.len() < 5 {
+                return Err(err("wanted score, time, date, seed and name"));
+            }
+            entries.push(HighScore {
+                score: words[0].parse().map_err(|_| err("bad score"))?,
+                t: words[1].parse().map_err(|_| err("bad time"))?,
+                date: words[2].to_owned(),
+                seed: words[3].parse().map_err(|_| err("bad seed"))?,
+                name: words[4].to_owned(),
+            });
+        }
+        let mut table = HighScores { entries };
+        table.sort();
+        Ok(table)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut s = String::from("# score time date seed name\n");
+        for e in self.entries.iter() {
+            s.push_str(&format!("{} {:.3} {} {} {}\n", e.score, e.t, e.date, e.seed, e.name));
+        }
+        s
+    }
+
+    // missing is fine, thats just nobody having played yet
+    pub fn load(path: &Path) -> Result<HighScores, anyhow::Error> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => HighScores::parse(&s),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HighScores::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        write_atomic(path, &self.to_text())
+    }
+
+    fn sort(&mut self) {
+        // higher score first, same score goes to whoever lasted longer
+        self.entries.sort_by(|a, b| b.score.cmp(&a.score).then(b.t.partial_cmp(&a.t).unwrap()));
+        self.entries.truncate(MAX_SCORES);
+    }
+
+    pub fn qualifies(&self, score: i32) -> bool {
+        score > 0 && (self.entries.len() < MAX_SCORES || self.entries.iter().any(|e| score > e.score))
+    }
+
+    // where it ended up, if it made it
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        self.entries.push(entry.clone());
+        self.sort();
+        self.entries.iter().position(|e| *e == entry)
+    }
+}
+
+// writes to a temp file next to it then renames over the top
+pub fn write_atomic(path: &Path, text: &str) -> Result<(), anyhow::Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    {
+        use std::io::Write;
+        let mut f = std::fs::File::create(&tmp)?;
+        f.write_all(text.as_bytes())?;
+        f.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// yyyy-mm-dd for right now, utc
+pub fn today() -> String {
+    let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    date_from_days((secs / 86400) as i64)
+}
+
+// days since 1970-01-01 to a calendar date
+fn date_from_days(days: i64) -> String {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+    let doy = doe - (365*yoe + yoe/4 - yoe/100);
+    let mp = (5*doy + 2) / 153;
+    let d = doy - (153*mp + 2)/5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// Typing a name in after a run that made the table
+pub struct NameEntry {
+    pub summary: RunSummary,
+    pub name: String,
+}
+
+impl NameEntry {
+    pub fn new(summary: RunSummary) -> NameEntry {
+        NameEntry { summary, name: String::new() }
+    }
+
+    // letters, digits, backspace. gives back the entry once return is pressed
+    pub fn update(&mut self, inputs: &FrameInputs) -> Option<HighScore> {
+        for k in inputs.curr_keys.iter().filter(|k| inputs.key_press_or_repeat(**k)) {
+            let name = key_name(*k);
+            let c = match name.as_str() {
+                "Back" => {
+                    self.name.pop();
+                    None
+                },
+                "Space" => Some(' '),
+                n if n.len() == 1 => n.chars().next(),
+                n if n.len() == 4 && n.starts_with("Key") => n.chars().nth(3),
+                _ => None,
+            };
+            if let Some(c) = c {
+                if self.name.len() < MAX_NAME {
+                    self.name.push(c);
+                }
+            }
+        }
+        if inputs.key_rising(VirtualKeyCode::Return) {
+            let name = self.name.trim();
+            return Some(HighScore {
+                name: if name.is_empty() { "anon".to_owned() } else { name.to_owned() },
+                score: self.summary.score,
+                t: self.summary.t,
+                date: today(),
+                seed: self.summary.seed,
+            });
+        }
+        None
+    }
+
+    pub fn draw(&self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let r = inputs.screen_rect;
+        outputs.canvas.put_rect(r.child(0.1, 0.3, 0.8, 0.35), 4.0, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        outputs.canvas.put_rect(r.child(0.1, 0.3, 0.8, 0.35).dilate(0.005), 3.9, Vec4::new(1.0, 1.0, 0.0, 1.0));
+        let x = r.w/2.0;
+        outputs.glyphs.push_center_str("new high score", x, r.h * 0.37, 0.05, 0.05, 4.5, Vec4::new(1.0, 1.0, 0.0, 1.0));
+        outputs.glyphs.push_center_str(&format!("{}", self.summary.score), x, r.h * 0.45, 0.05, 0.05, 4.5, Vec4::new(1.0, 1.0, 1.0, 1.0));
+        let cursor = if (inputs.t * 2.0) as i32 % 2 == 0 { "_" } else { " " };
+        outputs.glyphs.push_center_str(&format!("{}{}", self.name, cursor), x, r.h * 0.53, 0.04, 0.04, 4.5, Vec4::new(0.0, 1.0, 1.0, 1.0));
+        outputs.glyphs.push_center_str("type your name then press return", x, r.h * 0.6, 0.025, 0.025, 4.5, Vec4::new(0.7, 0.7, 0.7, 1.0));
+    }
+}
+
+// the table, highlight is the row to pick out
+pub fn draw_high_scores(table: &HighScores, highlight: Option<usize>, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+    let r = inputs.screen_rect;
+    let panel = r.child(0.1, 0.1, 0.8, 0.8);
+    outputs.canvas.put_rect(panel, 4.0, Vec4::new(0.0, 0.0, 0.0, 1.0));
+    outputs.canvas.put_rect(panel.dilate(0.005), 3.9, Vec4::new(0.0, 1.0, 1.0, 1.0));
+    outputs.glyphs.push_center_str("high scores", r.w/2.0, r.h * 0.16, 0.05, 0.05, 4.5, Vec4::new(0.0, 1.0, 1.0, 1.0));
+    if table.entries.is_empty() {
+        outputs.glyphs.push_center_str("nobody yet", r.w/2.0, r.h * 0.5, 0.04, 0.04, 4.5, Vec4::new(0.7, 0.7, 0.7, 1.0));
+    }
+    let h = 0.03;
+    for (i, e) in table.entries.iter().enumerate() {
+        let y = r.h * 0.25 + i as f32 * h * 2.0;
+        let colour = if Some(i) == highlight { Vec4::new(1.0, 1.0, 0.0, 1.0) } else { Vec4::new(1.0, 1.0, 1.0, 1.0) };
+        let row = format!("{:>2}. {:<12} {:>6} {:>6.1}s {}", i + 1, e.name, e.score, e.t, e.date);
+        outputs.glyphs.push_str(&row, panel.x + 0.03, y, h * 0.8, h, 4.5, colour);
+    }
+    outputs.glyphs.push_center_str("tab - close", r.w/2.0, panel.bot() - 0.04, 0.025, 0.025, 4.5, Vec4::new(0.7, 0.7, 0.7, 1.0));
+}
+
+#[test]
+fn test_high_scores() {
+    assert_eq!(date_from_days(0), "1970-01-01");
+    assert_eq!(date_from_days(11016), "2000-02-29");
+    assert_eq!(date_from_days(19000), "2022-01-08");
+
+    let entry = |name: &str, score: i32| HighScore { name: name.to_owned(), score, t: score as f32, date: "2022-01-08".to_owned(), seed: 7 };
+    let mut table = HighScores::default();
+    for i in 1..=12 {
+        table.insert(entry("someone", i * 10));
+    }
+    assert_eq!(table.entries.len(), MAX_SCORES);
+    assert_eq!(table.entries[0].score, 120);
+    assert!(!table.qualifies(20));
+    assert_eq!(table.insert(entry("two words", 55)), Some(7));
+
+    let dir = std::env::temp_dir().join(format!("ssss_test_{}", std::process::id()));
+    let path = dir.join(FILE_NAME);
+    table.save(&path).unwrap();
+    assert!(!path.with_extension("tmp").exists());
+    let loaded = HighScores::load(&path).unwrap();
+    assert_eq!(loaded.entries[7].name, "two words");
+    assert_eq!(loaded, table);
+    assert_eq!(HighScores::load(&dir.join("nope.txt")).unwrap(), HighScores::default());
+    assert!(HighScores::parse("10 1.0 2022-01-01 nope bob\n").unwrap_err().to_string().starts_with("line 1"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
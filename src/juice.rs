@@ -0,0 +1,122 @@
+use crate::kmath::*;
+use crate::renderers::simple_renderer::SimpleCanvas;
+
+// Screen shake, hit-stop and flashes. Stuff asks for them through here and
+// everything gets scaled by intensity, so turning that down to 0 turns them off.
+// Flashes get scaled again by their own setting for people who need them gone.
+//
+// Shake is trauma based: things add trauma, it wears off over time and the shake
+// goes with trauma squared so little knocks barely move and big ones really do.
+// Hit-stop doesnt freeze anything itself, it piles up here and whoever is stepping
+// the game takes it and holds off stepping for that long (see FixedStep).
+
+const TRAUMA_DECAY: f32 = 1.5;
+const MAX_SHAKE: f32 = 0.03;
+const SHAKE_FREQ: f32 = 25.0;
+const MAX_HIT_STOP: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flash {
+    pub colour: Vec4,
+    pub t: f32,
+    pub duration: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Juice {
+    // 0 is off, 1 is normal
+    pub intensity: f32,
+    pub flashes: f32,
+    pub trauma: f32,
+    hit_stop: f32,
+    active: Vec<Flash>,
+    // its own clock so it keeps going once the game is over
+    t: f32,
+    seed: u32,
+}
+
+impl Juice {
+    pub fn new(seed: u32) -> Juice {
+        Juice {
+            intensity: 1.0,
+            flashes: 1.0,
+            trauma: 0.0,
+            hit_stop: 0.0,
+            active: Vec::new(),
+            t: 0.0,
+            seed,
+        }
+    }
+
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    pub fn hit_stop(&mut self, seconds: f32) {
+        self.hit_stop = (self.hit_stop + seconds * self.intensity).min(MAX_HIT_STOP);
+    }
+
+    // how long to hold off stepping for, only gets given out once
+    pub fn take_hit_stop(&mut self) -> f32 {
+        std::mem::replace(&mut self.hit_stop, 0.0)
+    }
+
+    // colour.w is how strong it starts, it fades out over duration
+    pub fn flash(&mut self, colour: Vec4, duration: f32) {
+        if self.intensity > 0.0 && self.flashes > 0.0 {
+            self.active.push(Flash { colour, t: self.t, duration });
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.t += dt;
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+        let t = self.t;
+        self.active.retain(|f| t - f.t < f.duration);
+    }
+
+    // where to move everything to for the shake right now
+    pub fn shake(&self) -> Vec2 {
+        let amount = MAX_SHAKE * self.intensity * self.trauma * self.trauma;
+        if amount == 0.0 {
+            return v2(0.0, 0.0);
+        }
+        let x = 2.0 * noise1d(self.t * SHAKE_FREQ, self.seed) - 1.0;
+        let y = 2.0 * noise1d(self.t * SHAKE_FREQ, khash(self.seed)) - 1.0;
+        amount * v2(x, y)
+    }
+
+    pub fn draw_flashes(&self, canvas: &mut SimpleCanvas, r: Rect, depth: f32) {
+        for f in self.active.iter() {
+            let k = ((self.t - f.t) / f.duration).clamp(0.0, 1.0);
+            let a = f.colour.w * self.intensity.min(1.0) * self.flashes * (1.0 - k);
+            canvas.put_rect(r, depth, v4(f.colour.x, f.colour.y, f.colour.z, a));
+        }
+    }
+}
+
+#[test]
+fn test_juice() {
+    let mut j = Juice::new(1);
+    j.add_trauma(0.7);
+    j.add_trauma(0.7);
+    assert_eq!(j.trauma, 1.0);
+    j.update(0.1);
+    assert!(j.shake().magnitude() > 0.0);
+    j.update(1.0);
+    assert_eq!(j.shake(), v2(0.0, 0.0));
+
+    j.hit_stop(0.1);
+    j.hit_stop(0.1);
+    assert!((j.take_hit_stop() - 0.2).abs() < 0.0001);
+    assert_eq!(j.take_hit_stop(), 0.0);
+
+    // turned off nothing happens
+    j.intensity = 0.0;
+    j.add_trauma(1.0);
+    j.hit_stop(0.1);
+    j.flash(v4(1.0, 1.0, 1.0, 1.0), 0.1);
+    assert_eq!(j.shake(), v2(0.0, 0.0));
+    assert_eq!(j.take_hit_stop(), 0.0);
+    assert!(j.active.is_empty());
+}
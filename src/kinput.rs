@@ -1,9 +1,13 @@
 use crate::kmath::*;
+use crate::actions::*;
 
 use std::collections::HashSet;
 use std::time::{SystemTime, Instant, Duration};
 
+use gilrs::{Gilrs, GamepadId, Button, Axis, EventType};
+
 use glutin::event::VirtualKeyCode;
+use glutin::event::MouseButton;
 
 use glutin::event::ElementState;
 use glutin::event::Event;
@@ -22,6 +26,96 @@ pub enum KeyStatus {
 
 // get rid of repeats did i do this already?
 
+pub const MAX_PADS: usize = 4;
+// sticks dont sit at exactly 0, anything inside this is 0 and it goes up to 1 from the edge of it
+const STICK_DEADZONE: f32 = 0.2;
+const TRIGGER_DEADZONE: f32 = 0.05;
+
+// One controller. Buttons go the same way as keys do, the sticks are raw -1 to 1
+// with y going down like everything else, deadzones get done when theyre read.
+#[derive(Clone, Default, Debug)]
+pub struct PadState {
+    pub connected: bool,
+    pub name: String,
+    pub curr_buttons: HashSet<Button>,
+    pub prev_buttons: HashSet<Button>,
+    pub left_stick: Vec2,
+    pub right_stick: Vec2,
+    // last frames, so pushing a stick over can be treated like a press
+    pub prev_left_stick: Vec2,
+    // 0 to 1
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+impl PadState {
+    // the triggers are analog so they go through the deadzone and count once theyre halfway
+    pub fn held(&self, button: Button) -> bool {
+        match button {
+            Button::LeftTrigger2 => self.trigger(true) > 0.5,
+            Button::RightTrigger2 => self.trigger(false) > 0.5,
+            _ => self.curr_buttons.contains(&button),
+        }
+    }
+    pub fn rising(&self, button: Button) -> bool {
+        self.curr_buttons.contains(&button) && !self.prev_buttons.contains(&button)
+    }
+    pub fn falling(&self, button: Button) -> bool {
+        !self.curr_buttons.contains(&button) && self.prev_buttons.contains(&button)
+    }
+    pub fn left(&self) -> Vec2 {
+        stick_deadzone(self.left_stick)
+    }
+    pub fn right(&self) -> Vec2 {
+        stick_deadzone(self.right_stick)
+    }
+    pub fn trigger(&self, left: bool) -> f32 {
+        let v = if left { self.left_trigger } else { self.right_trigger };
+        ((v - TRIGGER_DEADZONE) / (1.0 - TRIGGER_DEADZONE)).clamp(0.0, 1.0)
+    }
+    // left stick just got pushed past halfway in this direction
+    pub fn flicked(&self, dir: Vec2) -> bool {
+        self.left_stick.dot(dir) > 0.5 && self.prev_left_stick.dot(dir) <= 0.5
+    }
+}
+
+// round rather than per axis so diagonals dont get cut off, and rescaled so it
+// doesnt jump from 0 to 0.2 at the edge
+fn stick_deadzone(v: Vec2) -> Vec2 {
+    let m = v.magnitude();
+    if m <= STICK_DEADZONE {
+        return Vec2::new(0.0, 0.0);
+    }
+    let k = ((m - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+    v * (k / m)
+}
+
+// Everything that went down or up since the last frame, in the order it happened.
+// t is on the same clock as FrameInputs::t so its somewhere between the last frame
+// and this one. Pad ones are by slot in FrameInputs::pads.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEventKind {
+    KeyDown(VirtualKeyCode),
+    KeyUp(VirtualKeyCode),
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    PadDown(usize, Button),
+    PadUp(usize, Button),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    pub t: f32,
+    pub kind: InputEventKind,
+}
+
+// controllers coming and going, by which slot in FrameInputs::pads theyre in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadEvent {
+    Connected(usize),
+    Disconnected(usize),
+}
+
 #[derive(Clone)]
 pub struct FrameInputs {
     pub screen_rect: Rect,
@@ -36,13 +130,45 @@ pub struct FrameInputs {
     pub rmb: KeyStatus,
     pub mmb: KeyStatus,
     pub scroll_delta: f32,
+
+    // MAX_PADS of them, first come first served
+    pub pads: Vec<PadState>,
+    pub pad_events: Vec<PadEvent>,
+
+    // a tap that goes down and up inside one frame is still in here, and still
+    // counts as held for that frame and released on the next
+    pub events: Vec<InputEvent>,
+
     pub t: f32,
     pub dt: f32,
+    pub alpha: f32,
     pub frame: u32,
     pub seed: u32,
 }
 
 impl FrameInputs {
+    pub fn new(screen_rect: Rect, seed: u32) -> FrameInputs {
+        FrameInputs { 
+            screen_rect, 
+            mouse_pos: Vec2::new(0.0, 0.0), 
+            mouse_delta: Vec2::new(0.0, 0.0), 
+            scroll_delta: 0.0,
+            curr_keys: HashSet::new(),
+            prev_keys: HashSet::new(),
+            repeat_keys: HashSet::new(),
+            lmb: KeyStatus::Released, 
+            rmb: KeyStatus::Released, 
+            mmb: KeyStatus::Released, 
+            pads: vec![PadState::default(); MAX_PADS],
+            pad_events: Vec::new(),
+            events: Vec::new(),
+            t: 0.0,
+            dt: 0.0,
+            alpha: 1.0,
+            frame: 0,
+            seed,
+        }
+    }
     pub fn key_held(&self, keycode: VirtualKeyCode) -> bool {
         self.curr_keys.contains(&keycode)
     }
@@ -55,7 +181,118 @@ impl FrameInputs {
     pub fn key_falling(&self, keycode: VirtualKeyCode) -> bool {
         !self.curr_keys.contains(&keycode) && self.prev_keys.contains(&keycode)
     }
+    // What this frame gets to see, and gets ready for the next one. Anything that
+    // went down this frame shows up as held even if its already back up, then as
+    // released next frame, so quick taps dont go missing.
+    pub fn end_frame(&mut self) -> FrameInputs {
+        let mut state = self.clone();
+        // pads get polled at the end so theirs can be out of order
+        state.events.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+        let mut tapped = Vec::new();
+        for e in self.events.iter() {
+            match e.kind {
+                InputEventKind::KeyDown(k) => { state.curr_keys.insert(k); },
+                InputEventKind::PadDown(i, b) => { state.pads[i].curr_buttons.insert(b); },
+                InputEventKind::MouseDown(m) if self.mouse_button(m) == KeyStatus::JustReleased => tapped.push(m),
+                _ => {},
+            }
+        }
+        for m in tapped.iter() {
+            if let Some(status) = state.mouse_button_mut(*m) {
+                *status = KeyStatus::JustPressed;
+            }
+        }
+
+        self.prev_keys = state.curr_keys.clone();
+        for (pad, seen) in self.pads.iter_mut().zip(state.pads.iter()) {
+            pad.prev_buttons = seen.curr_buttons.clone();
+            pad.prev_left_stick = pad.left_stick;
+        }
+        self.pad_events.clear();
+        self.events.clear();
+        self.repeat_keys = HashSet::new();
+        self.seed = khash(self.seed.wrapping_mul(196513497));
+        self.scroll_delta = 0.0;
+        for m in [MouseButton::Left, MouseButton::Middle, MouseButton::Right] {
+            if tapped.contains(&m) {
+                continue;
+            }
+            if let Some(status) = self.mouse_button_mut(m) {
+                *status = match *status {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
+            }
+        }
+        state
+    }
+
+    pub fn pad(&self, i: usize) -> Option<&PadState> {
+        self.pads.get(i).filter(|p| p.connected)
+    }
+    pub fn any_pad_rising(&self, button: Button) -> bool {
+        self.pads.iter().any(|p| p.rising(button))
+    }
+    pub fn mouse_button(&self, button: MouseButton) -> KeyStatus {
+        match button {
+            MouseButton::Left => self.lmb,
+            MouseButton::Right => self.rmb,
+            MouseButton::Middle => self.mmb,
+            MouseButton::Other(_) => KeyStatus::Released,
+        }
+    }
+    pub fn mouse_button_mut(&mut self, button: MouseButton) -> Option<&mut KeyStatus> {
+        match button {
+            MouseButton::Left => Some(&mut self.lmb),
+            MouseButton::Right => Some(&mut self.rmb),
+            MouseButton::Middle => Some(&mut self.mmb),
+            MouseButton::Other(_) => None,
+        }
+    }
+    // pad bindings are read off the given pad
+    pub fn binding_held(&self, pad: usize, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(k) => self.key_held(k),
+            Binding::Mouse(m) => matches!(self.mouse_button(m), KeyStatus::Pressed | KeyStatus::JustPressed),
+            Binding::Pad(b) => self.pads.get(pad).map(|p| p.held(b)).unwrap_or(false),
+        }
+    }
+    pub fn binding_rising(&self, pad: usize, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(k) => self.key_rising(k),
+            Binding::Mouse(m) => self.mouse_button(m) == KeyStatus::JustPressed,
+            Binding::Pad(b) => self.pads.get(pad).map(|p| p.rising(b)).unwrap_or(false),
+        }
+    }
+    pub fn action_held(&self, map: &ActionMap, action: Action) -> bool {
+        map.bindings(action).any(|b| self.binding_held(map.pad, b))
+    }
+    pub fn action_rising(&self, map: &ActionMap, action: Action) -> bool {
+        map.bindings(action).any(|b| self.binding_rising(map.pad, b))
+    }
+}
+
+// keys that can be named in scripts and config files, by their VirtualKeyCode name
+const NAMED_KEYS: [VirtualKeyCode; 62] = [
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E, VirtualKeyCode::F, VirtualKeyCode::G,
+    VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J, VirtualKeyCode::K, VirtualKeyCode::L, VirtualKeyCode::M, VirtualKeyCode::N,
+    VirtualKeyCode::O, VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R, VirtualKeyCode::S, VirtualKeyCode::T, VirtualKeyCode::U,
+    VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X, VirtualKeyCode::Y, VirtualKeyCode::Z,
+    VirtualKeyCode::Key0, VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5, VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+    VirtualKeyCode::Up, VirtualKeyCode::Down, VirtualKeyCode::Left, VirtualKeyCode::Right,
+    VirtualKeyCode::Space, VirtualKeyCode::Return, VirtualKeyCode::Escape, VirtualKeyCode::Back, VirtualKeyCode::Tab,
+    VirtualKeyCode::LShift, VirtualKeyCode::RShift, VirtualKeyCode::LControl, VirtualKeyCode::RControl, VirtualKeyCode::LAlt, VirtualKeyCode::RAlt,
+    VirtualKeyCode::Numpad0, VirtualKeyCode::Numpad1, VirtualKeyCode::Numpad2, VirtualKeyCode::Numpad3, VirtualKeyCode::Numpad4,
+    VirtualKeyCode::Numpad5, VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad7, VirtualKeyCode::Numpad8, VirtualKeyCode::Numpad9,
+    VirtualKeyCode::NumpadAdd,
+];
+
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    NAMED_KEYS.iter().copied().find(|k| key_name(*k).eq_ignore_ascii_case(name))
+}
+
+pub fn key_name(key: VirtualKeyCode) -> String {
+    format!("{:?}", key)
 }
+
 // yeah how do I get / ignore key repeats
 // should be like
 // released
@@ -71,30 +308,89 @@ pub struct EventAggregator {
     t_last: Instant,
     instant_mouse_pos: Vec2,
     current: FrameInputs,
+    gilrs: Option<Gilrs>,
+    pad_ids: [Option<GamepadId>; MAX_PADS],
 }
 
 impl EventAggregator {
     pub fn new(xres: f32, yres: f32) -> EventAggregator {
-        EventAggregator { 
+        let mut ea = EventAggregator { 
             xres, 
             yres, 
             t_last: Instant::now(),
             instant_mouse_pos: Vec2::new(0.0, 0.0),
-            current: FrameInputs { 
-                screen_rect: Rect::new(0.0, 0.0, xres/yres, 1.0, ), 
-                mouse_pos: Vec2::new(0.0, 0.0), 
-                mouse_delta: Vec2::new(0.0, 0.0), 
-                scroll_delta: 0.0,
-                curr_keys: HashSet::new(),
-                prev_keys: HashSet::new(),
-                repeat_keys: HashSet::new(),
-                lmb: KeyStatus::Released, 
-                rmb: KeyStatus::Released, 
-                mmb: KeyStatus::Released, 
-                t: 0.0,
-                dt: 0.0,
-                frame: 0,
-                seed: SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or(Duration::from_nanos(34123123)).subsec_nanos(),
+            current: FrameInputs::new(
+                Rect::new(0.0, 0.0, xres/yres, 1.0), 
+                SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or(Duration::from_nanos(34123123)).subsec_nanos(),
+            ),
+            gilrs: Gilrs::new().map_err(|e| println!("no gamepads: {}", e)).ok(),
+            pad_ids: [None; MAX_PADS],
+        };
+        // ones already plugged in dont get a connected event
+        let ids: Vec<GamepadId> = ea.gilrs.iter().flat_map(|g| g.gamepads().map(|(id, _)| id)).collect();
+        for id in ids {
+            ea.pad_connected(id);
+        }
+        ea
+    }
+
+    // on the same clock as FrameInputs::t
+    fn now(&self) -> f32 {
+        self.current.t + self.t_last.elapsed().as_secs_f32()
+    }
+
+    fn push_event(&mut self, kind: InputEventKind) {
+        let t = self.now();
+        self.current.events.push(InputEvent { t, kind });
+    }
+
+    fn pad_connected(&mut self, id: GamepadId) {
+        if self.pad_ids.contains(&Some(id)) {
+            return;
+        }
+        if let Some(slot) = self.pad_ids.iter().position(|p| p.is_none()) {
+            self.pad_ids[slot] = Some(id);
+            let name = self.gilrs.as_ref().map(|g| g.gamepad(id).name().to_owned()).unwrap_or_default();
+            self.current.pads[slot] = PadState { connected: true, name, ..Default::default() };
+            self.current.pad_events.push(PadEvent::Connected(slot));
+        }
+    }
+
+    fn poll_pads(&mut self) {
+        while let Some(gilrs::Event { id, event, time }) = self.gilrs.as_mut().and_then(|g| g.next_event()) {
+            // these only get looked at once a frame, gilrs knows when they really happened
+            let t = self.now() - SystemTime::now().duration_since(time).unwrap_or_default().as_secs_f32();
+            if event == EventType::Connected {
+                self.pad_connected(id);
+                continue;
+            }
+            let slot = match self.pad_ids.iter().position(|p| *p == Some(id)) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            let pad = &mut self.current.pads[slot];
+            match event {
+                EventType::Disconnected => {
+                    *pad = PadState::default();
+                    self.pad_ids[slot] = None;
+                    self.current.pad_events.push(PadEvent::Disconnected(slot));
+                },
+                EventType::ButtonPressed(button, _) => {
+                    pad.curr_buttons.insert(button);
+                    self.current.events.push(InputEvent { t, kind: InputEventKind::PadDown(slot, button) });
+                },
+                EventType::ButtonReleased(button, _) => {
+                    pad.curr_buttons.remove(&button);
+                    self.current.events.push(InputEvent { t, kind: InputEventKind::PadUp(slot, button) });
+                },
+                EventType::ButtonChanged(Button::LeftTrigger2, v, _) => pad.left_trigger = v,
+                EventType::ButtonChanged(Button::RightTrigger2, v, _) => pad.right_trigger = v,
+                // gilrs has up as positive
+                EventType::AxisChanged(Axis::LeftStickX, v, _) => pad.left_stick.x = v,
+                EventType::AxisChanged(Axis::LeftStickY, v, _) => pad.left_stick.y = -v,
+                EventType::AxisChanged(Axis::RightStickX, v, _) => pad.right_stick.x = v,
+                EventType::AxisChanged(Axis::RightStickY, v, _) => pad.right_stick.y = -v,
+                _ => {},
             }
         }
     }
@@ -113,31 +409,22 @@ impl EventAggregator {
                             self.current.repeat_keys.insert(*virtual_code);
                         } else {
                             self.current.curr_keys.insert(*virtual_code);
+                            self.push_event(InputEventKind::KeyDown(*virtual_code));
                         }
-                    } else {
-                        self.current.curr_keys.remove(virtual_code);
+                    } else if self.current.curr_keys.remove(virtual_code) {
+                        self.push_event(InputEventKind::KeyUp(*virtual_code));
                     }
                 },
 
-                MouseInput { button: glutin::event::MouseButton::Left, state, ..} => {
-                    if *state == ElementState::Pressed {
-                        self.current.lmb = KeyStatus::JustPressed;
-                    } else {
-                        self.current.lmb = KeyStatus::JustReleased;
-                    }
-                },
-                MouseInput { button: glutin::event::MouseButton::Middle, state, ..} => {
-                    if *state == ElementState::Pressed {
-                        self.current.mmb = KeyStatus::JustPressed;
-                    } else {
-                        self.current.mmb = KeyStatus::JustReleased;
-                    }
-                },
-                MouseInput { button: glutin::event::MouseButton::Right, state, ..} => {
-                    if *state == ElementState::Pressed {
-                        self.current.rmb = KeyStatus::JustPressed;
-                    } else {
-                        self.current.rmb = KeyStatus::JustReleased;
+                MouseInput { button, state, ..} => {
+                    if let Some(status) = self.current.mouse_button_mut(*button) {
+                        if *state == ElementState::Pressed {
+                            *status = KeyStatus::JustPressed;
+                            self.push_event(InputEventKind::MouseDown(*button));
+                        } else {
+                            *status = KeyStatus::JustReleased;
+                            self.push_event(InputEventKind::MouseUp(*button));
+                        }
                     }
                 },
 
@@ -183,20 +470,59 @@ impl EventAggregator {
                 self.current.frame += 1;
                 self.current.mouse_delta = self.instant_mouse_pos - self.current.mouse_pos;
                 self.current.mouse_pos = self.instant_mouse_pos;
-                let state = self.current.clone();
-                self.current.prev_keys = self.current.curr_keys.clone();
-                self.current.repeat_keys = HashSet::new();
-                self.current.seed = khash(self.current.seed * 196513497);
-                self.current.scroll_delta = 0.0;
-                self.current.lmb = match self.current.lmb {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
-                self.current.mmb = match self.current.mmb {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
-                self.current.rmb = match self.current.rmb {KeyStatus::JustPressed | KeyStatus::Pressed => KeyStatus::Pressed, KeyStatus::JustReleased | KeyStatus::Released => KeyStatus::Released};
-
-                return Some(state);
+                self.poll_pads();
+                return Some(self.current.end_frame());
             },
             _ => {},
         }
 
         None
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_pad() {
+    let mut pad = PadState { connected: true, ..Default::default() };
+    pad.left_stick = Vec2::new(0.1, -0.1);
+    assert_eq!(pad.left(), Vec2::new(0.0, 0.0));
+    pad.left_stick = Vec2::new(0.6, 0.0);
+    assert!((pad.left().x - 0.5).abs() < 0.0001);
+    pad.left_stick = Vec2::new(1.0, 1.0);
+    assert!((pad.left().magnitude() - 1.0).abs() < 0.0001);
+    assert!(pad.flicked(Vec2::new(1.0, 0.0)));
+
+    pad.curr_buttons.insert(Button::South);
+    assert!(pad.rising(Button::South));
+    pad.prev_buttons = pad.curr_buttons.clone();
+    pad.curr_buttons.clear();
+    assert!(pad.falling(Button::South) && !pad.held(Button::South));
+
+    // resting a finger on the trigger isnt firing
+    pad.right_trigger = 0.3;
+    assert!(pad.trigger(false) > 0.0 && !pad.held(Button::RightTrigger2));
+    pad.right_trigger = 0.8;
+    assert!(pad.held(Button::RightTrigger2));
+}
+
+#[test]
+fn test_taps() {
+    let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), 1);
+    // space and the mouse both go down and up again before the frame
+    inputs.events.push(InputEvent { t: 0.001, kind: InputEventKind::KeyDown(VirtualKeyCode::Space) });
+    inputs.events.push(InputEvent { t: 0.002, kind: InputEventKind::MouseDown(MouseButton::Left) });
+    inputs.events.push(InputEvent { t: 0.003, kind: InputEventKind::KeyUp(VirtualKeyCode::Space) });
+    inputs.events.push(InputEvent { t: 0.004, kind: InputEventKind::MouseUp(MouseButton::Left) });
+    inputs.lmb = KeyStatus::JustReleased;
+    let frame = inputs.end_frame();
+    assert!(frame.key_rising(VirtualKeyCode::Space));
+    assert!(frame.lmb == KeyStatus::JustPressed);
+    assert_eq!(frame.events.len(), 4);
+    assert!(frame.events.windows(2).all(|w| w[0].t <= w[1].t));
+
+    let frame = inputs.end_frame();
+    assert!(frame.key_falling(VirtualKeyCode::Space));
+    assert!(frame.lmb == KeyStatus::JustReleased);
+    assert!(frame.events.is_empty());
+    let frame = inputs.end_frame();
+    assert!(!frame.key_falling(VirtualKeyCode::Space));
+    assert!(frame.lmb == KeyStatus::Released);
+}
@@ -18,7 +18,10 @@ pub fn remap(x: f32, from_low: f32, from_high: f32, to_low: f32, to_high: f32) -
 }
 
 pub fn cubic_bezier(start: Vec2, c1: Vec2, c2: Vec2, end: Vec2, t: f32) -> Vec2 {
-    start.lerp(c1.lerp(c2.lerp(end, t), t), t)
+    let a = start.lerp(c1, t);
+    let b = c1.lerp(c2, t);
+    let c = c2.lerp(end, t);
+    a.lerp(b, t).lerp(b.lerp(c, t), t)
 }
 
 // t 0..1
@@ -26,6 +29,40 @@ pub fn smoothstep(t: f32) -> f32 {
     t * t * (3. - 2. * t)
 }
 
+// ray casts, dir has to be normalized. gives how far along the ray it hits, 0 if it starts inside
+pub fn ray_rect(origin: Vec2, dir: Vec2, r: Rect) -> Option<f32> {
+    let mut t_in = 0.0f32;
+    let mut t_out = f32::INFINITY;
+    for (o, d, lo, hi) in [(origin.x, dir.x, r.left(), r.right()), (origin.y, dir.y, r.top(), r.bot())] {
+        if d == 0.0 {
+            if o < lo || o > hi {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (lo - o) / d;
+        let t2 = (hi - o) / d;
+        t_in = t_in.max(t1.min(t2));
+        t_out = t_out.min(t1.max(t2));
+    }
+    if t_in <= t_out { Some(t_in) } else { None }
+}
+
+pub fn ray_circle(origin: Vec2, dir: Vec2, c: Vec2, r: f32) -> Option<f32> {
+    let oc = origin - c;
+    let b = oc.x * dir.x + oc.y * dir.y;
+    let k = oc.x * oc.x + oc.y * oc.y - r * r;
+    if k <= 0.0 {
+        return Some(0.0);
+    }
+    let disc = b * b - k;
+    // pointing away or missing
+    if b > 0.0 || disc < 0.0 {
+        return None;
+    }
+    Some(-b - disc.sqrt())
+}
+
 /***************************************************
  * RNG
  ***************************************************/
@@ -63,8 +100,8 @@ pub fn chance(seed: u32, percent: f32) -> bool {
 }
 
 pub fn noise1d(t: f32, seed: u32) -> f32 {
-    let hstart = kuniform(seed + 489172373 * t.floor() as u32, 0.0, 1.0);
-    let hend = kuniform(seed + 489172373 * (t.floor() + 1.0) as u32, 0.0, 1.0);
+    let hstart = kuniform(seed.wrapping_add(489172373u32.wrapping_mul(t.floor() as u32)), 0.0, 1.0);
+    let hend = kuniform(seed.wrapping_add(489172373u32.wrapping_mul((t.floor() + 1.0) as u32)), 0.0, 1.0);
     lerp(hstart, hend, smoothstep(t.fract()))
 }
 
@@ -100,7 +137,7 @@ pub fn noise2d(x: f32, y: f32, seed: u32) -> f32 {
  pub fn v3(x: f32, y: f32, z: f32) -> Vec3 {Vec3{x,y,z}}
  pub fn v4(x: f32, y: f32, z: f32, w: f32) -> Vec4 {Vec4{x,y,z,w}}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
@@ -115,6 +152,7 @@ impl Vec2 {
     pub fn mul_scalar(&self, scalar: f32) -> Vec2 { Vec2::new(self.x * scalar, self.y * scalar) }
     pub fn div_scalar(&self, scalar: f32) -> Vec2 { Vec2::new(self.x / scalar, self.y / scalar) }
     pub fn magnitude(&self) -> f32 { (self.x*self.x + self.y*self.y).sqrt() }
+    pub fn dot(&self, other: Vec2) -> f32 { self.x*other.x + self.y*other.y }
     pub fn dist(&self, other: Vec2) -> f32 { (*self - other).magnitude() }
     pub fn normalize(&self) -> Vec2 { let m = self.magnitude(); if m == 0.0 { *self } else { self.div_scalar(self.magnitude()) }}
     pub fn lerp(&self, other: Vec2, t: f32) -> Vec2 { Vec2::new(self.x*(1.0-t) + other.x*(t), self.y*(1.0-t) + other.y*(t)) }
@@ -748,6 +786,33 @@ fn print_mat(a: [f32; 16]) {
 
 // make a lovely test that shows it all super clear
 
+#[test]
+fn test_cubic_bezier() {
+    let start = v2(0.0, 0.0);
+    let c1 = v2(0.0, 1.0);
+    let c2 = v2(1.0, 1.0);
+    let end = v2(1.0, 0.0);
+    assert_eq!(cubic_bezier(start, c1, c2, end, 0.0), start);
+    assert_eq!(cubic_bezier(start, c1, c2, end, 1.0), end);
+    assert_eq!(cubic_bezier(start, c1, c2, end, 0.5), v2(0.5, 0.75));
+}
+
+#[test]
+fn test_raycast() {
+    let r = Rect::new(1.0, -0.5, 1.0, 1.0);
+    assert_eq!(ray_rect(v2(0.0, 0.0), v2(1.0, 0.0), r), Some(1.0));
+    assert_eq!(ray_rect(v2(0.0, 1.0), v2(1.0, 0.0), r), None);
+    assert_eq!(ray_rect(v2(3.0, 0.0), v2(1.0, 0.0), r), None);
+    assert_eq!(ray_rect(v2(1.5, 0.0), v2(1.0, 0.0), r), Some(0.0));
+    let d = v2(1.0, 1.0).normalize();
+    assert!((ray_rect(v2(0.0, -1.0), d, r).unwrap() - 2.0f32.sqrt()).abs() < 0.0001);
+
+    assert_eq!(ray_circle(v2(0.0, 0.0), v2(1.0, 0.0), v2(2.0, 0.0), 0.5), Some(1.5));
+    assert_eq!(ray_circle(v2(0.0, 0.0), v2(-1.0, 0.0), v2(2.0, 0.0), 0.5), None);
+    assert_eq!(ray_circle(v2(0.0, 0.6), v2(1.0, 0.0), v2(2.0, 0.0), 0.5), None);
+    assert_eq!(ray_circle(v2(2.1, 0.0), v2(1.0, 0.0), v2(2.0, 0.0), 0.5), Some(0.0));
+}
+
 #[test]
 fn test_view() {
     let pos = Vec3::new(0.0, 0.0, 0.0);
@@ -7,23 +7,104 @@ mod kinput;
 
 mod renderers;
 mod scene;
+mod fixed_step;
 mod widgets;
 
 mod game;
+mod archetype;
+mod stage;
+mod pattern;
+mod weapon;
+mod powerup;
+mod particles;
+mod juice;
 mod root_scene;
+mod pause;
+mod title;
+mod picker;
 
 mod audio;
 mod sound_instance;
 
+mod headless;
+mod replay;
+mod highscore;
+mod settings;
+mod actions;
+
 mod video;
 mod texture_buffer;
 
 use crate::application::*;
+use crate::root_scene::*;
+use crate::replay::*;
+use crate::scene::*;
+use crate::game::*;
+use crate::stage::*;
+use crate::settings::*;
+use crate::title::TitleScreen;
+use crate::picker::DemoPicker;
+
+// value following a --flag on the command line
+pub fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|a| a == name).and_then(|i| args.get(i + 1)).map(|s| s.as_str())
+}
+
+// everything --demo and the picker (f2) can start, add prototypes and test scenes here
+pub fn demos() -> DemoRegistry {
+    DemoRegistry::default()
+        .with::<TitleScreen>("title")
+        .with::<Game>("game")
+}
+
+// a game set up however the command line says, players overrides --players
+pub fn game_scene(args: &[String], players: Option<u32>) -> Box<dyn Demo> {
+    let mut game = match arg_value(args, "--stage") {
+        Some(path) => Game::with_stage(random_seed(), Stage::load(path).unwrap_or_else(|e| panic!("couldnt load stage {}: {}", path, e))),
+        None => Game::default(),
+    };
+    let mut rules = Rules::from_args(args);
+    if let Some(players) = players {
+        rules.players = players;
+    }
+    game.set_rules(rules);
+    match arg_value(args, "--record") {
+        Some(path) => Box::new(Recording::new(path, game)),
+        None => Box::new(game),
+    }
+}
 
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--headless") {
+        headless::headless_main(&args);
+        return;
+    }
+
+    let scene: Box<dyn Demo> = if let Some(path) = arg_value(&args, "--replay") {
+        Box::new(Playback::new(Replay::load(path).unwrap_or_else(|e| panic!("couldnt load replay {}: {}", path, e))))
+    } else if let Some(name) = arg_value(&args, "--demo") {
+        // anything it doesnt know gets the list to pick from
+        demos().make(name).unwrap_or_else(|| {
+            println!("no demo called {}, theres: {}", name, demos().names().join(" "));
+            Box::new(DemoPicker::new(demos()))
+        })
+    } else {
+        Box::new(TitleScreen::new(&args))
+    };
+
+    let mut settings = Settings::load(&Settings::path()).unwrap_or_else(|e| {
+        println!("couldnt load settings: {}", e);
+        Settings::default()
+    });
+    // --fx 0 to 1 for how much shake and hit-stop there is, just for this time
+    if let Some(fx) = arg_value(&args, "--fx") {
+        settings.fx = fx.parse::<f32>().expect("bad --fx").clamp(0.0, 1.0);
+    }
+
     let event_loop = glutin::event_loop::EventLoop::new();
-    let mut application = Application::new(&event_loop);
+    let mut application = Application::new(&event_loop, RootScene::with_scene(scene, settings));
     
     event_loop.run(move |event, _, _| {
         application.handle_event(event);
@@ -0,0 +1,222 @@
+use crate::kmath::*;
+use crate::renderers::simple_renderer::SimpleCanvas;
+
+// Bits that fly off things. Theres a fixed pool of them, new ones take the next
+// free slot and dead ones get swapped to the end so nothing gets allocated once
+// its going. If the pool is full new ones just dont happen.
+//
+// An emitter says what a burst looks like: how many, how fast, which way, how
+// long they last and how much drag slows them down. Size and colour go from
+// start to end over each ones life, `ease` bends that curve (1 is straight,
+// more hangs onto the start for longer, less gets to the end sooner).
+
+pub const MAX_PARTICLES: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    pub count: u32,
+    pub speed: (f32, f32),
+    pub lifetime: (f32, f32),
+    // radians either side of the direction its pointed
+    pub spread: f32,
+    pub drag: f32,
+    pub size: (f32, f32),
+    pub colour: (Vec4, Vec4),
+    pub ease: f32,
+}
+
+impl Emitter {
+    pub fn with_colour(&self, start: Vec4) -> Emitter {
+        Emitter { colour: (start, self.colour.1), ..*self }
+    }
+
+    pub fn with_count(&self, count: u32) -> Emitter {
+        Emitter { count, ..*self }
+    }
+}
+
+pub const ENEMY_DEATH: Emitter = Emitter {
+    count: 24,
+    speed: (0.1, 0.5),
+    lifetime: (0.3, 0.7),
+    spread: PI,
+    drag: 4.0,
+    size: (0.012, 0.0),
+    colour: (Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 }, Vec4 { x: 1.0, y: 0.3, z: 0.0, w: 1.0 }),
+    ease: 0.5,
+};
+
+pub const PLAYER_DEATH: Emitter = Emitter {
+    count: 60,
+    speed: (0.05, 0.7),
+    lifetime: (0.5, 1.2),
+    spread: PI,
+    drag: 2.5,
+    size: (0.016, 0.0),
+    colour: (Vec4 { x: 1.0, y: 1.0, z: 0.6, w: 1.0 }, Vec4 { x: 0.8, y: 0.1, z: 0.0, w: 1.0 }),
+    ease: 1.0,
+};
+
+pub const POWERUP_PICKUP: Emitter = Emitter {
+    count: 16,
+    speed: (0.2, 0.3),
+    lifetime: (0.3, 0.4),
+    spread: PI,
+    drag: 6.0,
+    size: (0.008, 0.0),
+    colour: (Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 }, Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 }),
+    ease: 2.0,
+};
+
+pub const EXHAUST: Emitter = Emitter {
+    count: 1,
+    speed: (0.15, 0.25),
+    lifetime: (0.1, 0.2),
+    spread: 0.3,
+    drag: 3.0,
+    size: (0.006, 0.001),
+    colour: (Vec4 { x: 1.0, y: 0.9, z: 0.4, w: 1.0 }, Vec4 { x: 0.9, y: 0.2, z: 0.0, w: 1.0 }),
+    ease: 1.0,
+};
+
+pub const SPARKS: Emitter = Emitter {
+    count: 1,
+    speed: (0.2, 0.6),
+    lifetime: (0.05, 0.15),
+    spread: PI/2.0,
+    drag: 1.0,
+    size: (0.004, 0.001),
+    colour: (Vec4 { x: 1.0, y: 1.0, z: 1.0, w: 1.0 }, Vec4 { x: 1.0, y: 1.0, z: 0.0, w: 1.0 }),
+    ease: 1.0,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub pos: Vec2,
+    pub v: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub drag: f32,
+    pub size: (f32, f32),
+    pub colour: (Vec4, Vec4),
+    pub ease: f32,
+}
+
+impl Particle {
+    // how far through its life, bent by ease
+    fn k(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0).powf(self.ease)
+    }
+
+    pub fn size(&self) -> f32 {
+        lerp(self.size.0, self.size.1, self.k())
+    }
+
+    pub fn colour(&self) -> Vec4 {
+        self.colour.0.lerp(self.colour.1, self.k())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Particles {
+    pool: Vec<Particle>,
+    // everything before this is alive
+    live: usize,
+    seed: u32,
+}
+
+impl Particles {
+    pub fn new(seed: u32) -> Particles {
+        let dead = Particle {
+            pos: v2(0.0, 0.0),
+            v: v2(0.0, 0.0),
+            age: 0.0,
+            lifetime: 0.0,
+            drag: 0.0,
+            size: (0.0, 0.0),
+            colour: (Vec4::new(0.0, 0.0, 0.0, 0.0), Vec4::new(0.0, 0.0, 0.0, 0.0)),
+            ease: 1.0,
+        };
+        Particles {
+            pool: vec![dead; MAX_PARTICLES],
+            live: 0,
+            seed,
+        }
+    }
+
+    pub fn live(&self) -> &[Particle] {
+        &self.pool[..self.live]
+    }
+
+    // dir is which way theyre pointed in radians, 0 is right
+    pub fn emit(&mut self, e: &Emitter, pos: Vec2, dir: f32) {
+        for _ in 0..e.count {
+            if self.live == MAX_PARTICLES {
+                return;
+            }
+            self.seed = khash(self.seed);
+            let h = self.seed;
+            let theta = dir + e.spread * (2.0 * krand(h) - 1.0);
+            let speed = kuniform(khash(h.wrapping_add(1)), e.speed.0, e.speed.1);
+            self.pool[self.live] = Particle {
+                pos,
+                v: speed * v2(theta.cos(), theta.sin()),
+                age: 0.0,
+                lifetime: kuniform(khash(h.wrapping_add(2)), e.lifetime.0, e.lifetime.1),
+                drag: e.drag,
+                size: e.size,
+                colour: e.colour,
+                ease: e.ease,
+            };
+            self.live += 1;
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let mut i = 0;
+        while i < self.live {
+            let p = &mut self.pool[i];
+            p.age += dt;
+            if p.age >= p.lifetime {
+                self.live -= 1;
+                self.pool.swap(i, self.live);
+                continue;
+            }
+            p.v = p.v * (-p.drag * dt).exp();
+            p.pos = p.pos + p.v * dt;
+            i += 1;
+        }
+    }
+
+    // back is how far to step them back along their velocity, like everything else
+    pub fn draw(&self, canvas: &mut SimpleCanvas, back: f32, depth: f32) {
+        for p in self.live() {
+            let size = p.size();
+            if size <= 0.0 {
+                continue;
+            }
+            canvas.put_rect((p.pos - back * p.v).rect_centered(size, size), depth, p.colour());
+        }
+    }
+}
+
+#[test]
+fn test_particles() {
+    let mut ps = Particles::new(1);
+    ps.emit(&ENEMY_DEATH, v2(0.5, 0.5), 0.0);
+    assert_eq!(ps.live().len(), ENEMY_DEATH.count as usize);
+    assert!(ps.live().iter().all(|p| p.lifetime >= 0.3 && p.lifetime <= 0.7));
+    assert_eq!(ps.live()[0].colour(), ENEMY_DEATH.colour.0);
+
+    // drag slows them, they shrink on the way out and theyre all gone by the longest lifetime
+    let v = ps.live()[0].v.magnitude();
+    ps.update(0.1);
+    assert!(ps.live()[0].v.magnitude() < v);
+    assert!(ps.live()[0].size() < ENEMY_DEATH.size.0);
+    ps.update(0.61);
+    assert!(ps.live().is_empty());
+
+    // a full pool drops new ones rather than growing
+    ps.emit(&PLAYER_DEATH.with_count(MAX_PARTICLES as u32 + 10), v2(0.0, 0.0), 0.0);
+    assert_eq!(ps.live().len(), MAX_PARTICLES);
+}
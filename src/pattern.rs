@@ -0,0 +1,159 @@
+use crate::kmath::*;
+use crate::stage::{number, Path};
+
+// Bullet patterns. Every <interval> seconds an enemy fires a shot, which is some
+// number of bullets going off in different directions, and from then on each
+// bullet looks after itself so none of this needs an enemy, a game or a window.
+//
+// fire none | <spread> <interval> [speed <s>] [accel <a>] [curve <x1> <y1> <x2> <y2> <x3> <y3> <duration>]
+//   spread: straight | fan <n> <degrees> | ring <n> | spiral <n> <degrees per shot> | aimed <n> <degrees>
+//   accel is how much faster it gets every second, negative slows it down
+//   curve is a bezier the bullet follows for <duration> seconds before carrying on
+//   in the direction it ended up going. its drawn for a bullet going left and gets
+//   turned to match whichever way the bullet is actually going
+
+pub const BULLET_SPEED: f32 = 0.6;
+const MIN_SPEED: f32 = 0.05;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Spread {
+    Straight,
+    Fan { n: u32, spread: f32 },
+    Ring { n: u32 },
+    Spiral { n: u32, turn: f32 },
+    Aimed { n: u32, spread: f32 },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub spread: Spread,
+    pub interval: f32,
+    pub speed: f32,
+    pub accel: f32,
+    pub curve: Option<Path>,
+}
+
+impl Pattern {
+    pub fn new(spread: Spread, interval: f32) -> Pattern {
+        Pattern { spread, interval, speed: BULLET_SPEED, accel: 0.0, curve: None }
+    }
+
+    // which way each bullet of the shot'th shot goes, pi is straight left
+    pub fn angles(&self, from: Vec2, target: Vec2, shot: u32) -> Vec<f32> {
+        let fan = |n: u32, spread: f32, middle: f32| -> Vec<f32> {
+            (0..n).map(|i| middle - spread/2.0 + spread * i as f32 / (n as f32 - 1.0).max(1.0)).collect()
+        };
+        match self.spread {
+            Spread::Straight => vec![PI],
+            Spread::Fan { n, spread } => fan(n, spread, PI),
+            Spread::Ring { n } => (0..n).map(|i| PI + TAU * i as f32 / n as f32).collect(),
+            Spread::Spiral { n, turn } => (0..n).map(|i| PI + turn * shot as f32 + TAU * i as f32 / n as f32).collect(),
+            Spread::Aimed { n, spread } => {
+                let d = target - from;
+                fan(n, spread, d.y.atan2(d.x))
+            },
+        }
+    }
+
+    pub fn shoot(&self, from: Vec2, target: Vec2, shot: u32) -> Vec<Bullet> {
+        self.angles(from, target, shot).into_iter().map(|theta| {
+            let curve = self.curve.as_ref().map(|c| c.rotated(theta - PI).moved(from));
+            let dir = match curve.as_ref() {
+                Some(c) => (c.c1 - c.start).normalize(),
+                None => v2(theta.cos(), theta.sin()),
+            };
+            Bullet { pos: from, v: self.speed * dir, accel: self.accel, curve, age: 0.0 }
+        }).collect()
+    }
+
+    pub fn parse(args: &[&str]) -> Result<Option<Pattern>, anyhow::Error> {
+        let mut words = args.iter();
+        let spread = match words.next() {
+            Some(&"none") => return Ok(None),
+            Some(&"straight") => Spread::Straight,
+            Some(&"fan") => Spread::Fan { n: number(words.next())? as u32, spread: number(words.next())?.to_radians() },
+            Some(&"ring") => Spread::Ring { n: number(words.next())? as u32 },
+            Some(&"spiral") => Spread::Spiral { n: number(words.next())? as u32, turn: number(words.next())?.to_radians() },
+            Some(&"aimed") => Spread::Aimed { n: number(words.next())? as u32, spread: number(words.next())?.to_radians() },
+            _ => return Err(anyhow::Error::msg("fire is none, straight, fan, ring, spiral or aimed")),
+        };
+        let mut pattern = Pattern::new(spread, number(words.next())?);
+        while let Some(w) = words.next() {
+            match *w {
+                "speed" => pattern.speed = number(words.next())?,
+                "accel" => pattern.accel = number(words.next())?,
+                "curve" => pattern.curve = Some(Path::parse(&mut words)?),
+                w => return Err(anyhow::Error::msg(format!("dont know '{}'", w))),
+            }
+        }
+        Ok(Some(pattern))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bullet {
+    pub pos: Vec2,
+    pub v: Vec2,
+    pub accel: f32,
+    pub curve: Option<Path>,
+    pub age: f32,
+}
+
+impl Bullet {
+    pub fn update(&mut self, dt: f32) {
+        self.age += dt;
+        if let Some(curve) = self.curve.as_ref() {
+            let s = self.age / curve.duration;
+            if s < 1.0 {
+                let p = curve.at(s);
+                if dt > 0.0 {
+                    self.v = (p - self.pos) / dt;
+                }
+                self.pos = p;
+                return;
+            }
+            // off the end, keeps whatever velocity it had last
+            self.curve = None;
+        }
+        if self.accel != 0.0 {
+            let speed = (self.v.magnitude() + self.accel * dt).max(MIN_SPEED);
+            self.v = speed * self.v.normalize();
+        }
+        self.pos = self.pos + dt * self.v;
+    }
+}
+
+#[test]
+fn test_patterns() {
+    let from = v2(1.0, 0.5);
+    let fan = Pattern::new(Spread::Fan { n: 3, spread: 1.0 }, 1.0);
+    assert_eq!(fan.angles(from, from, 0), vec![PI - 0.5, PI, PI + 0.5]);
+
+    let spiral = Pattern::new(Spread::Spiral { n: 2, turn: 0.1 }, 1.0);
+    assert_eq!(spiral.angles(from, from, 3)[0], PI + 0.3);
+
+    let aimed = Pattern::new(Spread::Aimed { n: 1, spread: 0.0 }, 1.0);
+    let b = &aimed.shoot(from, v2(1.0, 1.0), 0)[0];
+    assert!(b.v.x.abs() < 0.0001 && b.v.y > 0.0);
+
+    // speeds up
+    let mut b = Pattern::parse(&["straight", "1", "accel", "1"]).unwrap().unwrap().shoot(from, from, 0).remove(0);
+    for _ in 0..120 {
+        b.update(1.0 / 120.0);
+    }
+    assert!((b.v.magnitude() - (BULLET_SPEED + 1.0)).abs() < 0.001);
+
+    // follows the curve then keeps going
+    let p = Pattern::parse(&["straight", "1", "curve", "-0.1", "0", "-0.2", "0.1", "-0.2", "0.2", "1"]).unwrap().unwrap();
+    let mut b = p.shoot(from, from, 0).remove(0);
+    for _ in 0..120 {
+        b.update(1.0 / 120.0);
+    }
+    assert!(b.pos.dist(from + v2(-0.2, 0.2)) < 0.005);
+    b.update(1.0 / 120.0);
+    assert!(b.pos.y > 0.7);
+
+    assert_eq!(Pattern::parse(&["none"]).unwrap(), None);
+    assert!(Pattern::parse(&["ring", "8"]).is_err());
+    assert!(Pattern::parse(&["ring", "8", "1", "wobble"]).is_err());
+}
@@ -0,0 +1,54 @@
+use gilrs::Button;
+
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+use crate::title::TitleScreen;
+use crate::widgets::{Menu, Nav};
+
+// Goes on top of the game while its stopped. Escape, start or resume takes it off again,
+// quitting drops the game and goes back to the title rather than closing the window.
+
+const ROWS: [&str; 4] = ["resume", "restart", "options", "quit to title"];
+
+pub struct PauseMenu {
+    menu: Menu,
+    change: Option<SceneChange>,
+}
+
+impl PauseMenu {
+    pub fn new() -> PauseMenu {
+        PauseMenu { menu: Menu::new(), change: None }
+    }
+}
+
+impl Demo for PauseMenu {
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let nav = Nav::from_inputs(inputs);
+        self.menu.update(ROWS.len(), nav, inputs);
+        if nav.back || inputs.any_pad_rising(Button::Start) {
+            self.change = Some(SceneChange::Pop(Transition::Slide));
+        } else if let Some(i) = self.menu.chosen(nav, inputs) {
+            self.change = Some(match i {
+                0 => SceneChange::Pop(Transition::Slide),
+                1 => SceneChange::Restart,
+                2 => SceneChange::Options,
+                // same command line it started with so co-op and the rest still apply
+                _ => SceneChange::Switch(Box::new(TitleScreen::new(&std::env::args().collect::<Vec<_>>())), Transition::Fade),
+            });
+        }
+
+        let r = inputs.screen_rect;
+        outputs.canvas.put_rect(r, 3.5, Vec4::new(0.0, 0.0, 0.0, 0.5));
+        let panel = r.child(0.3, 0.25, 0.4, 0.45);
+        outputs.canvas.put_rect(panel, 4.0, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        outputs.canvas.put_rect(panel.dilate(0.005), 3.9, Vec4::new(1.0, 1.0, 1.0, 1.0));
+        outputs.glyphs.push_center_str("paused", r.w/2.0, r.h * 0.31, 0.05, 0.05, 4.5, Vec4::new(1.0, 1.0, 1.0, 1.0));
+        let rows: Vec<String> = ROWS.iter().map(|s| s.to_string()).collect();
+        self.menu.draw(&rows, r.w/2.0, r.h * 0.42, 0.035, 4.5, outputs);
+    }
+
+    fn scene_change(&mut self) -> Option<SceneChange> {
+        self.change.take()
+    }
+}
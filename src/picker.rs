@@ -0,0 +1,43 @@
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+use crate::widgets::{Menu, Nav};
+
+// Everything in the registry in a list, picking one swaps it in for whatever was
+// running. F2 brings it up from anywhere, escape takes it off again.
+
+pub struct DemoPicker {
+    demos: DemoRegistry,
+    menu: Menu,
+    change: Option<SceneChange>,
+}
+
+impl DemoPicker {
+    pub fn new(demos: DemoRegistry) -> DemoPicker {
+        DemoPicker { demos, menu: Menu::new(), change: None }
+    }
+}
+
+impl Demo for DemoPicker {
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let nav = Nav::from_inputs(inputs);
+        let rows: Vec<String> = self.demos.names().iter().map(|s| s.to_string()).collect();
+        self.menu.update(rows.len(), nav, inputs);
+        if nav.back {
+            self.change = Some(SceneChange::Pop(Transition::Slide));
+        } else if let Some(i) = self.menu.chosen(nav, inputs) {
+            if let Some(demo) = self.demos.make(&rows[i]) {
+                self.change = Some(SceneChange::Switch(demo, Transition::Fade));
+            }
+        }
+
+        let r = inputs.screen_rect;
+        outputs.canvas.put_rect(r, 3.5, Vec4::new(0.0, 0.0, 0.0, 0.8));
+        outputs.glyphs.push_center_str("demos", r.w/2.0, r.h * 0.15, 0.05, 0.05, 4.5, Vec4::new(1.0, 1.0, 1.0, 1.0));
+        self.menu.draw(&rows, r.w/2.0, r.h * 0.3, 0.035, 4.5, outputs);
+    }
+
+    fn scene_change(&mut self) -> Option<SceneChange> {
+        self.change.take()
+    }
+}
@@ -0,0 +1,157 @@
+use crate::kmath::*;
+use crate::sound_instance::*;
+
+// Every kind of powerup. Which one comes floating in is a weighted pick, instant
+// ones just happen and the rest give the player an effect for `duration` seconds.
+// Picking up another one of the same stacks as its own effect with its own timer,
+// up to max_stacks, after that it replaces whichever was going to run out first.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerupKind {
+    ScreenClear,
+    Upgrade,
+    Shield,
+    Speed,
+    Cooling,
+    Multiplier,
+    ExtraLife,
+    Pierce,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PowerupType {
+    pub kind: PowerupKind,
+    pub name: &'static str,
+    // drawn in the middle of it
+    pub icon: &'static str,
+    pub weight: f32,
+    // 0 is instant
+    pub duration: f32,
+    pub max_stacks: usize,
+    pub colour: Vec4,
+    pub sound: u32,
+}
+
+pub const POWERUP_TYPES: [PowerupType; 8] = [
+    PowerupType { kind: PowerupKind::ScreenClear, name: "screen clear", icon: "@", weight: 2.0, duration: 0.0, max_stacks: 0, colour: Vec4 { x: 0.0, y: 1.0, z: 1.0, w: 1.0 }, sound: POWERUP },
+    PowerupType { kind: PowerupKind::Upgrade, name: "upgrade", icon: "^", weight: 3.0, duration: 0.0, max_stacks: 0, colour: Vec4 { x: 1.0, y: 1.0, z: 0.0, w: 1.0 }, sound: UPGRADE },
+    PowerupType { kind: PowerupKind::Shield, name: "shield", icon: "S", weight: 2.0, duration: 15.0, max_stacks: 3, colour: Vec4 { x: 0.3, y: 0.5, z: 1.0, w: 1.0 }, sound: SHIELD },
+    PowerupType { kind: PowerupKind::Speed, name: "speed", icon: ">", weight: 2.0, duration: 8.0, max_stacks: 2, colour: Vec4 { x: 0.2, y: 1.0, z: 0.2, w: 1.0 }, sound: SPEED },
+    PowerupType { kind: PowerupKind::Cooling, name: "cooling", icon: "C", weight: 2.0, duration: 6.0, max_stacks: 1, colour: Vec4 { x: 0.8, y: 0.9, z: 1.0, w: 1.0 }, sound: COOLING },
+    PowerupType { kind: PowerupKind::Multiplier, name: "multiplier", icon: "X", weight: 2.0, duration: 10.0, max_stacks: 3, colour: Vec4 { x: 1.0, y: 0.5, z: 0.0, w: 1.0 }, sound: MULTIPLIER },
+    PowerupType { kind: PowerupKind::ExtraLife, name: "extra life", icon: "L", weight: 1.0, duration: 0.0, max_stacks: 0, colour: Vec4 { x: 1.0, y: 0.2, z: 0.6, w: 1.0 }, sound: EXTRA_LIFE },
+    PowerupType { kind: PowerupKind::Pierce, name: "pierce", icon: "P", weight: 1.5, duration: 8.0, max_stacks: 1, colour: Vec4 { x: 1.0, y: 0.3, z: 0.3, w: 1.0 }, sound: PIERCE },
+];
+
+pub fn powerup_type(kind: PowerupKind) -> &'static PowerupType {
+    POWERUP_TYPES.iter().find(|p| p.kind == kind).unwrap()
+}
+
+// weighted pick, seed decides
+pub fn pick_powerup(seed: u32) -> PowerupKind {
+    let total: f32 = POWERUP_TYPES.iter().map(|p| p.weight).sum();
+    let mut x = krand(seed) * total;
+    for p in POWERUP_TYPES.iter() {
+        if x < p.weight {
+            return p.kind;
+        }
+        x -= p.weight;
+    }
+    POWERUP_TYPES[POWERUP_TYPES.len() - 1].kind
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Effect {
+    pub kind: PowerupKind,
+    pub until: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Effects {
+    pub active: Vec<Effect>,
+}
+
+impl Effects {
+    pub fn add(&mut self, kind: PowerupKind, t: f32) {
+        let pt = powerup_type(kind);
+        if self.count(kind) >= pt.max_stacks {
+            if let Some(i) = self.soonest(kind) {
+                self.active.remove(i);
+            }
+        }
+        self.active.push(Effect { kind, until: t + pt.duration });
+    }
+
+    pub fn expire(&mut self, t: f32) {
+        self.active.retain(|e| e.until > t);
+    }
+
+    pub fn count(&self, kind: PowerupKind) -> usize {
+        self.active.iter().filter(|e| e.kind == kind).count()
+    }
+
+    pub fn has(&self, kind: PowerupKind) -> bool {
+        self.count(kind) > 0
+    }
+
+    // uses one up, the one that was going to run out first. false if there wasnt one
+    pub fn take(&mut self, kind: PowerupKind) -> bool {
+        match self.soonest(kind) {
+            Some(i) => {
+                self.active.remove(i);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // how long until the last one of these runs out
+    pub fn remaining(&self, kind: PowerupKind, t: f32) -> f32 {
+        self.active.iter().filter(|e| e.kind == kind).map(|e| e.until - t).fold(0.0, f32::max)
+    }
+
+    pub fn speed(&self) -> f32 {
+        1.0 + 0.4 * self.count(PowerupKind::Speed) as f32
+    }
+
+    // doubles for every one going
+    pub fn multiplier(&self) -> i32 {
+        1 << self.count(PowerupKind::Multiplier)
+    }
+
+    fn soonest(&self, kind: PowerupKind) -> Option<usize> {
+        self.active.iter().enumerate()
+            .filter(|(_, e)| e.kind == kind)
+            .min_by(|a, b| a.1.until.partial_cmp(&b.1.until).unwrap())
+            .map(|(i, _)| i)
+    }
+}
+
+#[test]
+fn test_effects() {
+    let mut e = Effects::default();
+    e.add(PowerupKind::Multiplier, 0.0);
+    e.add(PowerupKind::Multiplier, 5.0);
+    assert_eq!(e.multiplier(), 4);
+    // first one runs out, second keeps going
+    e.expire(12.0);
+    assert_eq!(e.multiplier(), 2);
+    e.expire(15.0);
+    assert_eq!(e.multiplier(), 1);
+
+    // past max stacks it refreshes the oldest
+    for t in 0..4 {
+        e.add(PowerupKind::Shield, t as f32);
+    }
+    assert_eq!(e.count(PowerupKind::Shield), 3);
+    assert!(e.take(PowerupKind::Shield));
+    assert_eq!(e.remaining(PowerupKind::Shield, 0.0), 18.0);
+    assert_eq!(e.active.iter().filter(|x| x.kind == PowerupKind::Shield).map(|x| x.until).fold(99.0, f32::min), 17.0);
+
+    let mut counts = [0; POWERUP_TYPES.len()];
+    for i in 0..1000 {
+        counts[POWERUP_TYPES.iter().position(|p| p.kind == pick_powerup(khash(i))).unwrap()] += 1;
+    }
+    assert!(counts.iter().all(|c| *c > 0));
+    assert!(counts[1] > counts[6]);
+}
@@ -37,14 +37,17 @@ pub fn glyph_buffer_to_canvas(buf: &GlyphBuffer, a: f32) -> CTCanvas {
 
 pub struct GlyphBuffer {
     pub buf: Vec<(char, Rect, f32, Vec4)>,
+    // added to everything pushed after its set, same as SimpleCanvas
+    pub offset: Vec2,
 }
 
 impl GlyphBuffer {
     pub fn new() -> GlyphBuffer {
-        GlyphBuffer { buf: Vec::new() }
+        GlyphBuffer { buf: Vec::new(), offset: Vec2::new(0.0, 0.0) }
     }
 
     pub fn push_glyph(&mut self, c: char, r: Rect, d: f32, colour: Vec4) {
+        let r = Rect::new(r.x + self.offset.x, r.y + self.offset.y, r.w, r.h);
         self.buf.push((c, r, d, colour));
     }
 
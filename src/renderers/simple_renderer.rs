@@ -103,6 +103,8 @@ impl SimpleRenderer {
 pub struct SimpleCanvas {
     a: f32,
     buf: Vec<u8>,
+    // added to everything put after its set, for shaking the screen
+    pub offset: Vec2,
 }
 
 impl SimpleCanvas {
@@ -110,6 +112,7 @@ impl SimpleCanvas {
         SimpleCanvas {
             a,
             buf: Vec::new(),
+            offset: Vec2::new(0.0, 0.0),
         }
     }
 
@@ -124,6 +127,7 @@ impl SimpleCanvas {
     }
 
     pub fn put_triangle(&mut self, p1: Vec2, p2: Vec2, p3: Vec2, depth: f32, colour: Vec4) {
+        let (p1, p2, p3) = (p1 + self.offset, p2 + self.offset, p3 + self.offset);
         self.put_float(p1.x/self.a);
         self.put_float(p1.y);
         self.put_float(depth);
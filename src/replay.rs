@@ -0,0 +1,383 @@
+use crate::fixed_step::*;
+use crate::game::*;
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+use crate::stage::*;
+use crate::archetype::parse_archetypes;
+use crate::highscore::RunSummary;
+use crate::settings::Settings;
+
+// A replay is the seed plus the GameInput for every step of one run, which is
+// all the simulation ever sees so playing it back gives the exact same run.
+// Steps are run length encoded since held keys dont change very often. If it was
+// a stage the whole stage file goes in too so it doesnt matter if that changes later,
+// same for the enemy types out of enemies.txt.
+
+const MAGIC: &[u8; 4] = b"SSRP";
+const VERSION: u8 = 6;
+
+pub struct Replay {
+    pub seed: u32,
+    pub final_score: i32,
+    pub dt: f32,
+    pub rules: Rules,
+    pub stage: Option<Stage>,
+    pub archetypes: String,
+    runs: Vec<(u32, GameInput)>,
+}
+
+impl Replay {
+    // for recording game from here on, call it before its first step
+    pub fn new(game: &Game) -> Replay {
+        Replay {
+            seed: game.seed(),
+            final_score: 0,
+            dt: SIM_DT,
+            rules: game.rules(),
+            stage: game.stage().cloned(),
+            archetypes: game.archetype_source().to_owned(),
+            runs: Vec::new(),
+        }
+    }
+
+    // the game as it was before the first step
+    pub fn new_game(&self) -> Game {
+        let mut game = Game::with_archetype_source(self.seed, self.archetypes.clone());
+        if let Some(stage) = self.stage.as_ref() {
+            game.set_stage(stage.clone());
+        }
+        game.set_rules(self.rules);
+        game
+    }
+
+    pub fn push(&mut self, mut input: GameInput) {
+        // nobody's in the spare slots so dont bother keeping them
+        for p in input.players.iter_mut().skip(self.rules.players as usize) {
+            *p = PlayerInput::IDLE;
+        }
+        match self.runs.last_mut() {
+            Some((n, last)) if *last == input => *n += 1,
+            _ => self.runs.push((1, input)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(|r| r.0 as usize).sum()
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = GameInput> + '_ {
+        self.runs.iter().flat_map(|(n, input)| std::iter::repeat_n(*input, *n as usize))
+    }
+
+    // plays the whole thing through a fresh game and hands it back at the end
+    pub fn simulate(&self) -> Game {
+        let mut game = self.new_game();
+        for input in self.inputs() {
+            game.update(&input);
+        }
+        game
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.extend_from_slice(&self.seed.to_le_bytes());
+        buf.extend_from_slice(&self.final_score.to_le_bytes());
+        buf.extend_from_slice(&self.dt.to_le_bytes());
+        buf.extend_from_slice(&self.rules.lives.to_le_bytes());
+        buf.extend_from_slice(&self.rules.health.to_le_bytes());
+        buf.extend_from_slice(&self.rules.players.to_le_bytes());
+        buf.push(self.rules.shared_lives as u8);
+        let stage = self.stage.as_ref().map(|s| s.source.as_bytes()).unwrap_or(&[]);
+        buf.extend_from_slice(&(stage.len() as u32).to_le_bytes());
+        buf.extend_from_slice(stage);
+        buf.extend_from_slice(&(self.archetypes.len() as u32).to_le_bytes());
+        buf.extend_from_slice(self.archetypes.as_bytes());
+        buf.extend_from_slice(&(self.runs.len() as u32).to_le_bytes());
+        for (n, input) in self.runs.iter() {
+            buf.extend_from_slice(&n.to_le_bytes());
+            buf.extend_from_slice(&input.screen_rect.w.to_le_bytes());
+            buf.extend_from_slice(&input.screen_rect.h.to_le_bytes());
+            buf.push(input.restart as u8);
+            for p in input.players.iter().take(self.rules.players as usize) {
+                buf.extend_from_slice(&p.steer.x.to_le_bytes());
+                buf.extend_from_slice(&p.steer.y.to_le_bytes());
+                buf.push(p.fire as u8 | (p.switch as u8) << 1);
+            }
+        }
+        buf
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Replay, anyhow::Error> {
+        let mut r = ByteReader { buf, pos: 0 };
+        if r.take(4)? != MAGIC {
+            return Err(anyhow::Error::msg("not a replay file"));
+        }
+        let version = r.take(1)?[0];
+        if version != VERSION {
+            return Err(anyhow::Error::msg(format!("replay version {} but we only know {}", version, VERSION)));
+        }
+        let seed = r.u32()?;
+        let final_score = r.u32()? as i32;
+        let dt = r.f32()?;
+        let rules = Rules { lives: r.u32()?, health: r.u32()?, players: r.u32()?, shared_lives: r.take(1)?[0] != 0 };
+        if rules.players == 0 || rules.players as usize > MAX_PLAYERS {
+            return Err(anyhow::Error::msg(format!("replay has {} players", rules.players)));
+        }
+        let stage_len = r.u32()? as usize;
+        let stage = match stage_len {
+            0 => None,
+            n => Some(Stage::parse(std::str::from_utf8(r.take(n)?)?)?),
+        };
+        let archetypes_len = r.u32()? as usize;
+        let archetypes = std::str::from_utf8(r.take(archetypes_len)?)?.to_owned();
+        // new_game counts on these parsing
+        parse_archetypes(&archetypes).map_err(|e| anyhow::Error::msg(format!("replay enemies: {}", e)))?;
+        let n_runs = r.u32()?;
+        let mut runs = Vec::new();
+        for _ in 0..n_runs {
+            let n = r.u32()?;
+            let w = r.f32()?;
+            let h = r.f32()?;
+            let restart = r.take(1)?[0] != 0;
+            let mut players = [PlayerInput::IDLE; MAX_PLAYERS];
+            for p in players.iter_mut().take(rules.players as usize) {
+                p.steer = Vec2::new(r.f32()?, r.f32()?);
+                let buttons = r.take(1)?[0];
+                p.fire = buttons & 1 != 0;
+                p.switch = buttons & 2 != 0;
+            }
+            runs.push((n, GameInput {
+                dt,
+                screen_rect: Rect::new(0.0, 0.0, w, h),
+                players,
+                restart,
+            }));
+        }
+        Ok(Replay { seed, final_score, dt, rules, stage, archetypes, runs })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), anyhow::Error> {
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Replay, anyhow::Error> {
+        Replay::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], anyhow::Error> {
+        if self.pos + n > self.buf.len() {
+            return Err(anyhow::Error::msg("replay file is cut short"));
+        }
+        self.pos += n;
+        Ok(&self.buf[self.pos - n..self.pos])
+    }
+    fn u32(&mut self) -> Result<u32, anyhow::Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    fn f32(&mut self) -> Result<f32, anyhow::Error> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+// Plays the game normally and writes out a replay of the run every time the player dies.
+// Restarting starts a new recording, to the same file. In a stage restarting carries on
+// from a checkpoint so that all goes in the one recording, which gets saved when it ends.
+pub struct Recording {
+    game: Game,
+    replay: Replay,
+    path: String,
+    restart_pending: bool,
+}
+
+impl Recording {
+    pub fn new(path: &str, game: Game) -> Recording {
+        Recording {
+            replay: Replay::new(&game),
+            game,
+            path: path.to_owned(),
+            restart_pending: false,
+        }
+    }
+}
+
+impl Demo for Recording {
+    fn update(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let mut input = GameInput::from_frame(inputs, &self.game.settings().bindings);
+        input.restart |= std::mem::take(&mut self.restart_pending);
+        let playing = self.game.alive() && !self.game.cleared();
+        if input.restart && self.replay.stage.is_none() {
+            self.game.update(&input);
+            // the new recording starts from the fresh game, which has already had this step
+            self.replay = Replay::new(&self.game);
+            self.replay.push(GameInput { restart: false, ..input });
+        } else if playing || self.replay.stage.is_some() {
+            self.replay.push(input);
+            self.game.update(&input);
+            if playing && (!self.game.alive() || self.game.cleared()) {
+                self.replay.final_score = self.game.score();
+                match self.replay.save(&self.path) {
+                    Ok(()) => println!("saved replay to {} ({} steps, score {})", self.path, self.replay.len(), self.replay.final_score),
+                    Err(e) => println!("couldnt save replay to {}: {}", self.path, e),
+                }
+            }
+        } else {
+            self.game.update(&input);
+        }
+        outputs.audio_events.append(&mut self.game.audio_events);
+        outputs.hit_stop = outputs.hit_stop.max(self.game.take_hit_stop());
+    }
+
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        self.game.draw(inputs.alpha, outputs);
+    }
+
+    fn run_over(&self) -> Option<RunSummary> {
+        self.game.run_over()
+    }
+
+    fn apply_settings(&mut self, settings: &Settings) {
+        self.game.set_settings(*settings);
+    }
+
+    fn pausable(&self) -> bool {
+        true
+    }
+
+    fn restart(&mut self) {
+        self.restart_pending = true;
+    }
+
+    fn players(&self) -> usize {
+        self.game.rules().players as usize
+    }
+}
+
+// Plays a replay back in the window, the keyboard is ignored.
+pub struct Playback {
+    game: Game,
+    replay: Replay,
+    inputs: Vec<GameInput>,
+    step: usize,
+    checked: bool,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            game: replay.new_game(),
+            inputs: replay.inputs().collect(),
+            replay,
+            step: 0,
+            checked: false,
+        }
+    }
+}
+
+impl Demo for Playback {
+    fn update(&mut self, _inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        if let Some(input) = self.inputs.get(self.step) {
+            self.game.update(input);
+            self.step += 1;
+            outputs.audio_events.append(&mut self.game.audio_events);
+            outputs.hit_stop = outputs.hit_stop.max(self.game.take_hit_stop());
+        } else if !self.checked {
+            self.checked = true;
+            println!("{}", check_message(&self.replay, &self.game));
+        }
+    }
+
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        self.game.draw(inputs.alpha, outputs);
+        let colour = if self.checked && self.game.score() != self.replay.final_score { v4(1.0, 0.0, 0.0, 1.0) } else { v4(1.0, 1.0, 1.0, 1.0) };
+        outputs.glyphs.push_str("replay", inputs.screen_rect.w - 0.2, 0.02, 0.03, 0.03, 2.1, colour);
+    }
+
+    fn apply_settings(&mut self, settings: &Settings) {
+        self.game.set_settings(*settings);
+    }
+
+    fn pausable(&self) -> bool {
+        true
+    }
+
+    // back to the start of the replay
+    fn restart(&mut self) {
+        let settings = *self.game.settings();
+        self.game = self.replay.new_game();
+        self.game.set_settings(settings);
+        self.step = 0;
+        self.checked = false;
+    }
+
+    fn players(&self) -> usize {
+        self.replay.rules.players as usize
+    }
+}
+
+pub fn check_message(replay: &Replay, game: &Game) -> String {
+    if game.score() == replay.final_score {
+        format!("replay ok: score {} matches", game.score())
+    } else {
+        format!("replay mismatch: recorded score {} but got {}", replay.final_score, game.score())
+    }
+}
+
+#[test]
+fn test_replay_roundtrip() {
+    let mut game = Game::new(555);
+    let mut replay = Replay::new(&game);
+    let mut i = 0usize;
+    while game.alive() && i < 120 * 60 {
+        let input = GameInput {
+            dt: SIM_DT,
+            screen_rect: Rect::new(0.0, 0.0, 1.0, 1.0),
+            players: [PlayerInput {
+                steer: Vec2::new(0.0, if (i / 90).is_multiple_of(2) { -1.0 } else { 1.0 }),
+                fire: !(i / 50).is_multiple_of(3),
+                switch: i.is_multiple_of(700),
+            }; MAX_PLAYERS],
+            restart: false,
+        };
+        replay.push(input);
+        game.update(&input);
+        i += 1;
+    }
+    replay.final_score = game.score();
+
+    let loaded = Replay::from_bytes(&replay.to_bytes()).unwrap();
+    assert_eq!(loaded.len(), i);
+    assert_eq!(loaded.archetypes, game.archetype_source());
+    let replayed = loaded.simulate();
+    assert_eq!(replayed.score(), replay.final_score);
+    assert_eq!(replayed.t().to_bits(), game.t().to_bits());
+    assert!(Replay::from_bytes(b"SSRP").is_err());
+
+    // restarting partway starts a new recording, that has to line up from its first step
+    let path = std::env::temp_dir().join("ssss_test_restart.rpl");
+    let mut rec = Recording::new(path.to_str().unwrap(), Game::new(7));
+    let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), 1);
+    inputs.dt = SIM_DT;
+    inputs.curr_keys.insert(glutin::event::VirtualKeyCode::Space);
+    let mut outputs = FrameOutputs::new(1.0);
+    for i in 0..1200 {
+        if i == 300 {
+            rec.restart();
+        }
+        rec.update(&inputs, &mut outputs);
+    }
+    let replayed = rec.replay.simulate();
+    assert_eq!(replayed.t().to_bits(), rec.game.t().to_bits());
+    assert_eq!(replayed.score(), rec.game.score());
+    std::fs::remove_file(path).ok();
+}
@@ -1,45 +1,323 @@
+use std::collections::HashSet;
+
 use glutin::event::VirtualKeyCode;
 use crate::scene::*;
+use crate::kinput::*;
 use crate::kmath::*;
+use crate::fixed_step::*;
+use crate::highscore::*;
+use crate::settings::*;
+use crate::actions::{Action, ActionMap};
+use crate::game::MAX_PLAYERS;
+use crate::pause::PauseMenu;
+use crate::picker::DemoPicker;
+
+// A stack of scenes. Only the top one gets stepped and gets the keys, the ones
+// under it still get drawn where they were, so a menu can sit over the game.
+// Scenes ask for changes to the stack through scene_change. Settings, high
+// scores and name entry are drawn over all of it and freeze everything.
 
-use crate::game::*;
+const TRANSITION_TIME: f32 = 0.3;
+
+struct Transitioning {
+    kind: Transition,
+    k: f32,
+    // whats on its way out, drawn until its gone
+    leaving: Option<Box<dyn Demo>>,
+    // the top of the stack only just went on
+    new_top: bool,
+}
 
 pub struct RootScene {
-    curr_scene: Option<Box<dyn Demo>>,
-    show_menu: bool,
+    scenes: Vec<Box<dyn Demo>>,
+    stepper: FixedStep,
+    transition: Option<Transitioning>,
+    quit: bool,
+
+    high_scores: HighScores,
+    name_entry: Option<NameEntry>,
+    show_scores: bool,
+    // the one that just went in, to pick out in the table
+    new_score: Option<usize>,
+    // so a finished run only gets offered once
+    last_run: Option<RunSummary>,
+
+    settings: Settings,
+    settings_menu: Option<SettingsMenu>,
 }
 
 impl RootScene {
-    pub fn new() -> RootScene {
+    pub fn with_scene(mut scene: Box<dyn Demo>, settings: Settings) -> RootScene {
+        scene.apply_settings(&settings);
+        let mut stepper = FixedStep::new();
+        stepper.speed = settings.speed;
         RootScene {
-            curr_scene: Some(init_demo::<Game>()),
-            show_menu: false,
+            scenes: vec![scene],
+            stepper,
+            transition: None,
+            quit: false,
+            high_scores: HighScores::load(&HighScores::path()).unwrap_or_else(|e| {
+                println!("couldnt load high scores: {}", e);
+                HighScores::default()
+            }),
+            name_entry: None,
+            show_scores: false,
+            new_score: None,
+            last_run: None,
+            settings,
+            settings_menu: None,
+        }
+    }
+
+    // once something has asked to quit
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    // the bindings of whoevers actually playing in the top scene
+    fn bindings_in_play(&self) -> &[ActionMap] {
+        let players = self.scenes.last().map(|s| s.players()).unwrap_or(0).min(MAX_PLAYERS);
+        &self.settings.bindings[..players]
+    }
+
+    // someones pad just went away, its state is already cleared so they stop
+    // moving but the game shouldnt carry on without them
+    fn pad_unplugged(&self, inputs: &FrameInputs) -> bool {
+        inputs.pad_events.iter().any(|e| match e {
+            PadEvent::Disconnected(slot) => self.bindings_in_play().iter().any(|map| map.pad == *slot),
+            PadEvent::Connected(_) => false,
+        })
+    }
+
+    fn change(&mut self, change: SceneChange) {
+        match change {
+            SceneChange::Push(mut scene, kind) => {
+                scene.apply_settings(&self.settings);
+                self.scenes.push(scene);
+                self.start_transition(kind, None, true);
+            },
+            SceneChange::Pop(kind) => {
+                let leaving = self.scenes.pop();
+                if self.scenes.is_empty() {
+                    self.quit = true;
+                }
+                self.start_transition(kind, leaving, false);
+            },
+            SceneChange::Replace(mut scene, kind) => {
+                scene.apply_settings(&self.settings);
+                let leaving = self.scenes.pop();
+                self.scenes.push(scene);
+                self.start_transition(kind, leaving, true);
+            },
+            SceneChange::Switch(mut scene, kind) => {
+                scene.apply_settings(&self.settings);
+                let leaving = self.scenes.pop();
+                self.scenes.clear();
+                self.scenes.push(scene);
+                self.start_transition(kind, leaving, true);
+            },
+            SceneChange::Restart => {
+                let leaving = self.scenes.pop();
+                if let Some(top) = self.scenes.last_mut() {
+                    top.restart();
+                }
+                self.start_transition(Transition::Cut, leaving, false);
+            },
+            SceneChange::Options => self.settings_menu = Some(SettingsMenu::new()),
+            SceneChange::HighScores => self.show_scores = true,
+            SceneChange::Quit => self.quit = true,
+        }
+    }
+
+    fn start_transition(&mut self, kind: Transition, leaving: Option<Box<dyn Demo>>, new_top: bool) {
+        self.transition = if kind == Transition::Cut {
+            None
+        } else {
+            Some(Transitioning { kind, k: 0.0, leaving, new_top })
+        };
+    }
+
+    // partway through a transition everythings frozen, its just drawing
+    fn draw_transition(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let tr = self.transition.as_mut().unwrap();
+        tr.k = (tr.k + inputs.dt / TRANSITION_TIME).min(1.0);
+        let frozen = frozen(inputs);
+        let h = inputs.screen_rect.h;
+        let n = self.scenes.len();
+        match tr.kind {
+            // the old lot fades to black then the new lot fades back in
+            Transition::Fade => {
+                let old = tr.k < 0.5;
+                let shown = if old && tr.new_top { n.saturating_sub(1) } else { n };
+                for scene in self.scenes[..shown].iter_mut() {
+                    scene.frame(&frozen, outputs);
+                }
+                if old {
+                    if let Some(leaving) = tr.leaving.as_mut() {
+                        leaving.frame(&frozen, outputs);
+                    }
+                }
+                let a = 1.0 - (2.0 * tr.k - 1.0).abs();
+                outputs.canvas.put_rect(inputs.screen_rect, 4.8, Vec4::new(0.0, 0.0, 0.0, a));
+            },
+            // new ones come down from the top, old ones go back up
+            _ => {
+                for (i, scene) in self.scenes.iter_mut().enumerate() {
+                    let y = if tr.new_top && tr.leaving.is_none() && i == n - 1 { -(1.0 - tr.k) * h } else { 0.0 };
+                    draw_offset(scene.as_mut(), &frozen, outputs, v2(0.0, y));
+                }
+                if let Some(leaving) = tr.leaving.as_mut() {
+                    draw_offset(leaving.as_mut(), &frozen, outputs, v2(0.0, -tr.k * h));
+                }
+            },
+        }
+        if tr.k >= 1.0 {
+            self.transition = None;
         }
     }
 }
 
+// for scenes that are showing but arent being played, where they were and deaf to keys
+fn frozen(inputs: &FrameInputs) -> FrameInputs {
+    let mut frozen = inputs.clone();
+    frozen.alpha = 1.0;
+    frozen.curr_keys = HashSet::new();
+    frozen.prev_keys = HashSet::new();
+    frozen.repeat_keys = HashSet::new();
+    frozen.lmb = KeyStatus::Released;
+    frozen.rmb = KeyStatus::Released;
+    frozen.mmb = KeyStatus::Released;
+    frozen.events = Vec::new();
+    for pad in frozen.pads.iter_mut() {
+        *pad = PadState { connected: pad.connected, name: pad.name.clone(), ..Default::default() };
+    }
+    frozen
+}
+
+fn draw_offset(scene: &mut dyn Demo, inputs: &FrameInputs, outputs: &mut FrameOutputs, offset: Vec2) {
+    outputs.canvas.offset = offset;
+    outputs.glyphs.offset = offset;
+    scene.frame(inputs, outputs);
+    outputs.canvas.offset = v2(0.0, 0.0);
+    outputs.glyphs.offset = v2(0.0, 0.0);
+}
+
 impl Demo for RootScene {
-    fn frame(&mut self, inputs: &crate::kinput::FrameInputs, outputs: &mut FrameOutputs) {
-        if let Some(curr) = self.curr_scene.as_mut() {
-            curr.frame(inputs, outputs);
-        } else {
-            self.show_menu = true;
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let overlay = self.name_entry.is_some() || self.show_scores || self.settings_menu.is_some();
+        let mut change = None;
+        if self.transition.is_some() {
+            self.draw_transition(inputs, outputs);
+        } else if let Some((top, under)) = self.scenes.split_last_mut() {
+            let frozen = frozen(inputs);
+            for scene in under.iter_mut() {
+                scene.frame(&frozen, outputs);
+            }
+            if overlay {
+                top.frame(&frozen, outputs);
+            } else {
+                self.stepper.frame(top.as_mut(), inputs, outputs);
+                change = top.scene_change();
+            }
+        }
+
+        let run = self.scenes.iter().rev().find_map(|s| s.run_over());
+        if let Some(summary) = run {
+            if run != self.last_run && self.high_scores.qualifies(summary.score) {
+                self.name_entry = Some(NameEntry::new(summary));
+            }
         }
+        self.last_run = run;
 
-        // if inputs.key_rising(VirtualKeyCode::Escape) {
-        //     self.show_menu = !self.show_menu;
-        // }
-        // if self.show_menu {
-        //     let ca = 12.0 / 14.0;
-        //     let ch = 0.02;
-        //     let cw = ch * ca;
-        //     let w = cw * 30.0;
+        let pausable = self.transition.is_none() && self.scenes.last().map(|s| s.pausable()).unwrap_or(false);
+        if let Some(menu) = self.settings_menu.as_mut() {
+            let before = self.settings;
+            let closed = menu.update(&mut self.settings, inputs);
+            if self.settings != before {
+                self.stepper.speed = self.settings.speed;
+                for scene in self.scenes.iter_mut() {
+                    scene.apply_settings(&self.settings);
+                }
+            }
+            if closed || inputs.key_rising(VirtualKeyCode::F1) {
+                self.settings_menu = None;
+                if let Err(e) = self.settings.save(&Settings::path()) {
+                    println!("couldnt save settings: {}", e);
+                }
+            }
+        } else if let Some(entry) = self.name_entry.as_mut() {
+            if let Some(score) = entry.update(inputs) {
+                self.new_score = self.high_scores.insert(score);
+                if let Err(e) = self.high_scores.save(&HighScores::path()) {
+                    println!("couldnt save high scores: {}", e);
+                }
+                self.name_entry = None;
+                self.show_scores = true;
+            } else if inputs.key_rising(VirtualKeyCode::Escape) {
+                self.name_entry = None;
+            }
+        } else if inputs.key_rising(VirtualKeyCode::Tab) || (self.show_scores && inputs.key_rising(VirtualKeyCode::Escape)) {
+            self.show_scores = !self.show_scores;
+            self.new_score = None;
+        } else if inputs.key_rising(VirtualKeyCode::F1) {
+            self.settings_menu = Some(SettingsMenu::new());
+        } else if change.is_some() {
+            // whatever the scene asked for goes first, the shortcuts can wait a frame
+        } else if self.transition.is_none() && inputs.key_rising(VirtualKeyCode::F2) {
+            change = Some(SceneChange::Push(Box::new(DemoPicker::new(crate::demos())), Transition::Slide));
+        } else if pausable && (self.bindings_in_play().iter().any(|map| inputs.action_rising(map, Action::Pause)) || self.pad_unplugged(inputs)) {
+            change = Some(SceneChange::Push(Box::new(PauseMenu::new()), Transition::Slide));
+        }
+        if let Some(change) = change {
+            self.change(change);
+        }
+
+        if let Some(menu) = self.settings_menu.as_mut() {
+            menu.draw(&self.settings, inputs, outputs);
+        } else if let Some(entry) = self.name_entry.as_ref() {
+            entry.draw(inputs, outputs);
+        } else if self.show_scores {
+            draw_high_scores(&self.high_scores, self.new_score, inputs, outputs);
+        }
+    }
+}
+
+#[test]
+fn test_pause() {
+    #[derive(Default)]
+    struct Solo;
+    impl Demo for Solo {
+        fn frame(&mut self, _inputs: &FrameInputs, _outputs: &mut FrameOutputs) {}
+        fn pausable(&self) -> bool { true }
+        fn players(&self) -> usize { 1 }
+    }
+    let mut root = RootScene::with_scene(Box::new(Solo), Settings::default());
+    let mut outputs = FrameOutputs::new(1.0);
+    let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), 1);
+    inputs.dt = SIM_DT;
 
-        //     let c = Vec4::grey(0.3);
-        //     let wcom = 1.0 - w;
-        //     outputs.canvas.put_rect(inputs.screen_rect.child(wcom/2.0, 0.0, w, 1.0), 4.0, c);
+    // nobodys on the second pad so losing it or pressing start on it doesnt matter
+    inputs.pad_events.push(PadEvent::Disconnected(1));
+    inputs.pads[1].curr_buttons.insert(gilrs::Button::Start);
+    root.frame(&inputs, &mut outputs);
+    assert_eq!(root.scenes.len(), 1);
 
-            // outputs.glyphs.push_center_str(self.demo_table[i].0, x, y_initial + i as f32 * ch, cw, ch * 0.8, 5.5, colour);
-        // }
+    inputs.pad_events = vec![PadEvent::Disconnected(0)];
+    root.frame(&inputs, &mut outputs);
+    assert_eq!(root.scenes.len(), 2);
+
+    // a scene leaving on the same frame pause goes down still gets to leave
+    struct Leaving;
+    impl Demo for Leaving {
+        fn frame(&mut self, _inputs: &FrameInputs, _outputs: &mut FrameOutputs) {}
+        fn pausable(&self) -> bool { true }
+        fn scene_change(&mut self) -> Option<SceneChange> { Some(SceneChange::Pop(Transition::Cut)) }
     }
-}
\ No newline at end of file
+    let mut root = RootScene::with_scene(Box::new(Solo), Settings::default());
+    root.scenes.push(Box::new(Leaving));
+    let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), 1);
+    inputs.dt = SIM_DT;
+    inputs.curr_keys.insert(VirtualKeyCode::Escape);
+    root.frame(&inputs, &mut outputs);
+    assert_eq!(root.scenes.len(), 1);
+}
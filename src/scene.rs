@@ -5,11 +5,38 @@ use crate::texture_buffer::*;
 use crate::kmath::*;
 use crate::audio::*;
 use crate::kinput::*;
+use crate::highscore::RunSummary;
+use crate::settings::Settings;
+use crate::game::MAX_PLAYERS;
 
 pub fn init_demo<T: Demo + Default + 'static>() -> Box<dyn Demo> {
     Box::new(T::default())
 }
 
+pub type DemoCtor = fn() -> Box<dyn Demo>;
+
+// Demos by name, so one binary can carry prototypes and test scenes next to the
+// game. --demo <name> starts one and the picker lists them all.
+#[derive(Clone, Default)]
+pub struct DemoRegistry {
+    demos: Vec<(&'static str, DemoCtor)>,
+}
+
+impl DemoRegistry {
+    pub fn with<T: Demo + Default + 'static>(mut self, name: &'static str) -> DemoRegistry {
+        self.demos.push((name, init_demo::<T>));
+        self
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.demos.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn make(&self, name: &str) -> Option<Box<dyn Demo>> {
+        self.demos.iter().find(|(n, _)| *n == name).map(|(_, f)| f())
+    }
+}
+
 pub struct FrameOutputs {
     pub canvas: SimpleCanvas,
     pub set_texture: Vec<(TextureBuffer, usize)>,
@@ -21,6 +48,8 @@ pub struct FrameOutputs {
     pub draw_mesh: Option<([f32;16], [f32;16], Vec3, Vec3)>,
 
     pub audio_events: Vec<u32>,
+    // seconds to stop stepping the simulation for, its the steppers job to do it
+    pub hit_stop: f32,
 }
 
 impl FrameOutputs {
@@ -31,6 +60,7 @@ impl FrameOutputs {
             set_texture: Vec::new(),
             draw_texture: Vec::new(),
             audio_events: Vec::new(),
+            hit_stop: 0.0,
             set_mesh: None,
             set_mesh_texture: None,
             draw_mesh: None,
@@ -38,6 +68,54 @@ impl FrameOutputs {
     }
 }
 
+// how the screen goes from one scene to the next
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    Cut,
+    Fade,
+    Slide,
+}
+
+// what a scene wants done to the stack, asked for after every frame
+pub enum SceneChange {
+    Push(Box<dyn Demo>, Transition),
+    Pop(Transition),
+    Replace(Box<dyn Demo>, Transition),
+    // throw out the whole stack for this one
+    Switch(Box<dyn Demo>, Transition),
+    // pop this one and start whatevers underneath over
+    Restart,
+    // the settings screen
+    Options,
+    HighScores,
+    Quit,
+}
+
 pub trait Demo {
+    // fixed rate simulation step, inputs.dt is always SIM_DT
+    fn update(&mut self, _inputs: &FrameInputs, _outputs: &mut FrameOutputs) {}
+    // once per rendered frame, inputs.alpha is how far along we are to the next update
     fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs);
-}
\ No newline at end of file
+    // a run thats over and could go in the high scores, if thats the kind of thing this is
+    fn run_over(&self) -> Option<RunSummary> { None }
+    // accessibility and that, whenever they change
+    fn apply_settings(&mut self, _settings: &Settings) {}
+    fn scene_change(&mut self) -> Option<SceneChange> { None }
+    // escape stops it and brings up the pause menu
+    fn pausable(&self) -> bool { false }
+    fn restart(&mut self) {}
+    // how many of the players bindings it listens to, the rest cant pause it
+    fn players(&self) -> usize { MAX_PLAYERS }
+}
+#[test]
+fn test_registry() {
+    #[derive(Default)]
+    struct Nothing;
+    impl Demo for Nothing {
+        fn frame(&mut self, _inputs: &FrameInputs, _outputs: &mut FrameOutputs) {}
+    }
+    let demos = DemoRegistry::default().with::<Nothing>("a").with::<Nothing>("b");
+    assert_eq!(demos.names(), vec!["a", "b"]);
+    assert!(demos.make("b").is_some());
+    assert!(demos.make("c").is_none());
+}
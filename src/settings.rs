@@ -0,0 +1,392 @@
+use std::path::{Path, PathBuf};
+
+use glutin::event::{MouseButton, VirtualKeyCode};
+
+use crate::actions::*;
+use crate::game::MAX_PLAYERS;
+use crate::highscore::{HighScores, write_atomic};
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+use crate::widgets::{Menu, Nav};
+
+// Accessibility and the like, kept in settings.txt next to the high scores.
+// One per line, anything left out stays at its default:
+//
+// flashes full | reduced | off       reduced also stops things blinking
+// fx <0-1>                           screen shake and hit-stop
+// palette normal | colourblind       enemies and bullets
+// outlines on | off                  dark outline round bullets
+// speed <0.5-1>                      how fast the game runs
+// bind p<n> <action> <binding>...    controls, see the top of src/actions.rs
+// p<n> <up> <down> <left> <right> <fire> <switch>
+//                                    the old way to set keys, still read
+
+const FILE_NAME: &str = "settings.txt";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flashes {
+    Full,
+    Reduced,
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Normal,
+    Colourblind,
+}
+
+// Okabe and Ito, minus the yellow and vermillion that bullets and the laser use
+const SAFE_COLOURS: [Vec4; 5] = [
+    Vec4 { x: 0.9, y: 0.6, z: 0.0, w: 1.0 },
+    Vec4 { x: 0.35, y: 0.7, z: 0.9, w: 1.0 },
+    Vec4 { x: 0.0, y: 0.6, z: 0.5, w: 1.0 },
+    Vec4 { x: 0.0, y: 0.45, z: 0.7, w: 1.0 },
+    Vec4 { x: 0.8, y: 0.6, z: 0.7, w: 1.0 },
+];
+
+impl Palette {
+    // colourblind snaps anything colourful to the nearest safe colour, greys stay grey
+    pub fn enemy(&self, c: Vec4) -> Vec4 {
+        if *self == Palette::Normal || c.x.max(c.y).max(c.z) - c.x.min(c.y).min(c.z) < 0.2 {
+            return c;
+        }
+        let d = |s: &Vec4| (s.x - c.x).powi(2) + (s.y - c.y).powi(2) + (s.z - c.z).powi(2);
+        let nearest = SAFE_COLOURS.iter().min_by(|a, b| d(a).partial_cmp(&d(b)).unwrap()).unwrap();
+        Vec4::new(nearest.x, nearest.y, nearest.z, c.w)
+    }
+
+    pub fn bullet(&self) -> Vec4 {
+        match self {
+            Palette::Normal => Vec4::new(1.0, 1.0, 0.0, 1.0),
+            Palette::Colourblind => Vec4::new(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub flashes: Flashes,
+    pub fx: f32,
+    pub palette: Palette,
+    pub outlines: bool,
+    pub speed: f32,
+    pub bindings: [ActionMap; MAX_PLAYERS],
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            flashes: Flashes::Full,
+            fx: 1.0,
+            palette: Palette::Normal,
+            outlines: false,
+            speed: 1.0,
+            bindings: default_bindings(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        HighScores::dir().join(FILE_NAME)
+    }
+
+    // how strong flashes get
+    pub fn flash_strength(&self) -> f32 {
+        match self.flashes {
+            Flashes::Full => 1.0,
+            Flashes::Reduced => 0.25,
+            Flashes::Off => 0.0,
+        }
+    }
+
+    // whether things are allowed to blink on and off
+    pub fn blink(&self) -> bool {
+        self.flashes == Flashes::Full
+    }
+
+    pub fn parse(s: &str) -> Result<Settings, anyhow::Error> {
+        let mut settings = Settings::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            settings.parse_line(&words).map_err(|e| anyhow::Error::msg(format!("line {}: {}", i + 1, e)))?;
+        }
+        Ok(settings)
+    }
+
+    fn parse_line(&mut self, words: &[&str]) -> Result<(), anyhow::Error> {
+        let arg = words.get(1).copied().unwrap_or("");
+        let number = || arg.parse::<f32>().map_err(|_| anyhow::Error::msg(format!("'{}' isnt a number", arg)));
+        let player = |w: &str| w.strip_prefix('p').and_then(|n| n.parse::<usize>().ok())
+            .filter(|i| *i >= 1 && *i <= MAX_PLAYERS)
+            .map(|i| i - 1)
+            .ok_or_else(|| anyhow::Error::msg(format!("no player '{}'", w)));
+        match words[0] {
+            "flashes" => self.flashes = match arg {
+                "full" => Flashes::Full,
+                "reduced" => Flashes::Reduced,
+                "off" => Flashes::Off,
+                _ => return Err(anyhow::Error::msg("flashes is full, reduced or off")),
+            },
+            "fx" => self.fx = number()?.clamp(0.0, 1.0),
+            "palette" => self.palette = match arg {
+                "normal" => Palette::Normal,
+                "colourblind" => Palette::Colourblind,
+                _ => return Err(anyhow::Error::msg("palette is normal or colourblind")),
+            },
+            "outlines" => self.outlines = match arg {
+                "on" => true,
+                "off" => false,
+                _ => return Err(anyhow::Error::msg("outlines is on or off")),
+            },
+            "speed" => self.speed = number()?.clamp(0.5, 1.0),
+            "bind" => {
+                let i = player(arg)?;
+                let name = words.get(2).copied().unwrap_or("");
+                let action = Action::from_name(name).ok_or_else(|| anyhow::Error::msg(format!("no action '{}'", name)))?;
+                self.bindings[i].clear(action);
+                for name in words[3..].iter() {
+                    let b = Binding::from_name(name).ok_or_else(|| anyhow::Error::msg(format!("cant bind '{}'", name)))?;
+                    self.bindings[i].bind(action, b);
+                }
+            },
+            w if w.starts_with('p') && w.len() == 2 => {
+                let i = player(w)?;
+                if words.len() != 7 {
+                    return Err(anyhow::Error::msg("wanted up, down, left, right, fire and switch"));
+                }
+                for (action, name) in ACTIONS.iter().zip(words[1..].iter()) {
+                    let key = key_from_name(name).ok_or_else(|| anyhow::Error::msg(format!("unknown key '{}'", name)))?;
+                    // just the key changes, pad buttons stay
+                    let map = &mut self.bindings[i];
+                    let old: Vec<Binding> = map.bindings(*action).filter(|b| matches!(b, Binding::Key(_))).collect();
+                    old.into_iter().for_each(|b| map.unbind(*action, b));
+                    map.bind(*action, Binding::Key(key));
+                }
+            },
+            w => return Err(anyhow::Error::msg(format!("dont know '{}'", w))),
+        }
+        Ok(())
+    }
+
+    pub fn to_text(self) -> String {
+        let mut s = String::new();
+        s.push_str(&format!("flashes {}\n", match self.flashes { Flashes::Full => "full", Flashes::Reduced => "reduced", Flashes::Off => "off" }));
+        s.push_str(&format!("fx {}\n", self.fx));
+        s.push_str(&format!("palette {}\n", match self.palette { Palette::Normal => "normal", Palette::Colourblind => "colourblind" }));
+        s.push_str(&format!("outlines {}\n", if self.outlines { "on" } else { "off" }));
+        s.push_str(&format!("speed {}\n", self.speed));
+        for (i, map) in self.bindings.iter().enumerate() {
+            for action in ACTIONS {
+                let names: Vec<String> = map.bindings(action).map(|b| b.name()).collect();
+                let line = format!("bind p{} {} {}", i + 1, action.name(), names.join(" "));
+                s.push_str(line.trim_end());
+                s.push('\n');
+            }
+        }
+        s
+    }
+
+    // missing is fine, thats just the defaults
+    pub fn load(path: &Path) -> Result<Settings, anyhow::Error> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => Settings::parse(&s),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        write_atomic(path, &self.to_text())
+    }
+}
+
+// The settings screen. Up and down to pick, left and right or return to change.
+// Controls are one player at a time, return on an action then press whatever
+// should do it, backspace clears it. Anything pressed thats already used by
+// someone else gets taken off them.
+pub struct SettingsMenu {
+    menu: Menu,
+    player: usize,
+    rebinding: Option<Action>,
+    // what the last rebind did, if its worth saying
+    message: String,
+}
+
+// rows before the actions
+const CONTROLS_ROW: usize = 5;
+
+impl SettingsMenu {
+    pub fn new() -> SettingsMenu {
+        SettingsMenu { menu: Menu::new(), player: 0, rebinding: None, message: String::new() }
+    }
+
+    fn rows(&self, s: &Settings) -> Vec<String> {
+        let mut rows = vec![
+            format!("flashes: {}", match s.flashes { Flashes::Full => "full", Flashes::Reduced => "reduced", Flashes::Off => "off" }),
+            format!("shake: {}%", (s.fx * 100.0).round()),
+            format!("palette: {}", match s.palette { Palette::Normal => "normal", Palette::Colourblind => "colourblind" }),
+            format!("bullet outlines: {}", if s.outlines { "on" } else { "off" }),
+            format!("game speed: {}%", (s.speed * 100.0).round()),
+            format!("controls: < p{} >", self.player + 1),
+        ];
+        for action in ACTIONS {
+            let labels: Vec<String> = s.bindings[self.player].bindings(action).map(|b| b.label()).collect();
+            rows.push(format!("{}: {}", action.name(), if labels.is_empty() { "-".to_owned() } else { labels.join(" ") }));
+        }
+        rows.push("back".to_owned());
+        rows
+    }
+
+    // the first thing pressed this frame that can be bound, escape is for getting out
+    // unless it can be bound
+    fn pressed(inputs: &FrameInputs, escape: bool) -> Option<Binding> {
+        let key = inputs.curr_keys.iter().copied()
+            .find(|k| (escape || *k != VirtualKeyCode::Escape) && inputs.key_rising(*k) && key_from_name(&key_name(*k)).is_some());
+        let mouse = [MouseButton::Left, MouseButton::Right, MouseButton::Middle].into_iter()
+            .find(|m| inputs.mouse_button(*m) == KeyStatus::JustPressed);
+        let pad = PAD_BUTTONS.iter().copied().find(|b| inputs.any_pad_rising(*b));
+        key.map(Binding::Key).or(mouse.map(Binding::Mouse)).or(pad.map(Binding::Pad))
+    }
+
+    // escape is p1s pause out of the box, so that one has to be able to have it back
+    fn escape_binds(player: usize, action: Action) -> bool {
+        default_bindings()[player].contains(action, Binding::Key(VirtualKeyCode::Escape))
+    }
+
+    // true once its closed
+    pub fn update(&mut self, settings: &mut Settings, inputs: &FrameInputs) -> bool {
+        if let Some(action) = self.rebinding {
+            let escape = SettingsMenu::escape_binds(self.player, action);
+            if !escape && inputs.key_rising(VirtualKeyCode::Escape) {
+                self.rebinding = None;
+            } else if let Some(binding) = SettingsMenu::pressed(inputs, escape) {
+                let found = conflicts(&settings.bindings, self.player, action, binding);
+                let reserved = found.iter().find_map(|c| match c {
+                    Conflict::Reserved(what) => Some(*what),
+                    Conflict::Action(..) => None,
+                });
+                if let Some(what) = reserved {
+                    self.message = format!("{} is for {}", binding.label(), what);
+                    self.rebinding = None;
+                    return false;
+                }
+                self.message.clear();
+                for c in found.iter() {
+                    if let Conflict::Action(p, a) = *c {
+                        settings.bindings[p].unbind(a, binding);
+                        if self.message.is_empty() {
+                            self.message = format!("took {} off p{} {}", binding.label(), p + 1, a.name());
+                        }
+                    }
+                }
+                settings.bindings[self.player].bind(action, binding);
+                self.rebinding = None;
+            }
+            return false;
+        }
+        let nav = Nav::from_inputs(inputs);
+        if nav.back {
+            return true;
+        }
+        let n = self.rows(settings).len();
+        self.menu.update(n, nav, inputs);
+        let chosen = self.menu.chosen(nav, inputs).is_some();
+        let action = self.menu.selected.checked_sub(CONTROLS_ROW + 1).and_then(|i| ACTIONS.get(i).copied());
+        if let Some(action) = action {
+            if inputs.key_rising(VirtualKeyCode::Back) {
+                settings.bindings[self.player].clear(action);
+            } else if chosen {
+                self.rebinding = Some(action);
+                self.message.clear();
+            }
+            return false;
+        }
+        let step = if nav.left { -1.0 } else if nav.right || chosen { 1.0 } else { return false };
+        match self.menu.selected {
+            0 => settings.flashes = match (settings.flashes, step > 0.0) {
+                (Flashes::Full, true) | (Flashes::Off, false) => Flashes::Reduced,
+                (Flashes::Reduced, true) => Flashes::Off,
+                (Flashes::Reduced, false) => Flashes::Full,
+                (f, _) => f,
+            },
+            1 => settings.fx = (settings.fx + 0.25 * step).clamp(0.0, 1.0),
+            2 => settings.palette = if settings.palette == Palette::Normal { Palette::Colourblind } else { Palette::Normal },
+            3 => settings.outlines = !settings.outlines,
+            4 => settings.speed = ((settings.speed + 0.1 * step) * 10.0).round().clamp(5.0, 10.0) / 10.0,
+            CONTROLS_ROW => self.player = (self.player as i32 + step as i32).rem_euclid(MAX_PLAYERS as i32) as usize,
+            _ => return chosen,
+        }
+        false
+    }
+
+    pub fn draw(&mut self, settings: &Settings, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        let r = inputs.screen_rect;
+        let panel = r.child(0.1, 0.1, 0.8, 0.8);
+        outputs.canvas.put_rect(panel, 4.0, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        outputs.canvas.put_rect(panel.dilate(0.005), 3.9, Vec4::new(1.0, 0.0, 1.0, 1.0));
+        outputs.glyphs.push_center_str("settings", r.w/2.0, r.h * 0.13, 0.05, 0.05, 4.5, Vec4::new(1.0, 0.0, 1.0, 1.0));
+        self.menu.draw(&self.rows(settings), r.w/2.0, r.h * 0.21, 0.025, 4.5, outputs);
+        let hint = match self.rebinding {
+            Some(action) if SettingsMenu::escape_binds(self.player, action) => format!("press something for p{} {}", self.player + 1, action.name()),
+            Some(action) => format!("press something for p{} {} - esc to stop", self.player + 1, action.name()),
+            None if !self.message.is_empty() => self.message.clone(),
+            None => "left right - change     backspace - unbind     esc - close".to_owned(),
+        };
+        outputs.glyphs.push_center_str(&hint, r.w/2.0, panel.bot() - 0.04, 0.02, 0.02, 4.5, Vec4::new(0.7, 0.7, 0.7, 1.0));
+    }
+}
+
+#[test]
+fn test_settings() {
+    let mut s = Settings { flashes: Flashes::Reduced, palette: Palette::Colourblind, speed: 0.7, ..Default::default() };
+    s.bindings[1].bind(Action::Fire, Binding::Mouse(MouseButton::Left));
+    s.bindings[2].clear(Action::Pause);
+    assert_eq!(Settings::parse(&s.to_text()).unwrap(), s);
+    assert!(!s.blink());
+
+    // missing lines stay default
+    assert_eq!(Settings::parse("outlines on\n").unwrap().fx, 1.0);
+    assert!(Settings::parse("flashes loud\n").unwrap_err().to_string().starts_with("line 1"));
+    assert!(Settings::parse("p5 W S A D Space Q\n").is_err());
+    assert!(Settings::parse("p1 W S A D Space Nope\n").is_err());
+    assert!(Settings::parse("bind p1 jump Space\n").is_err());
+    // the old key lines still work and leave the pad alone
+    let old = Settings::parse("p2 I K J L H U\n").unwrap();
+    assert!(old.bindings[1].contains(Action::Fire, Binding::Key(VirtualKeyCode::H)));
+    assert!(!old.bindings[1].contains(Action::Fire, Binding::Key(VirtualKeyCode::RControl)));
+    assert!(old.bindings[1].contains(Action::Fire, Binding::Pad(gilrs::Button::South)));
+
+    // everything colourful comes out as one of the safe ones
+    let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+    assert_eq!(Palette::Normal.enemy(red), red);
+    assert!(SAFE_COLOURS.contains(&Palette::Colourblind.enemy(red)));
+    assert_eq!(Palette::Colourblind.enemy(Vec4::grey(0.5)), Vec4::grey(0.5));
+
+    // p1 pause can get escape back after losing it, anywhere else escape just stops
+    let mut s = Settings::default();
+    s.bindings[0].clear(Action::Pause);
+    let mut menu = SettingsMenu::new();
+    let press = |s: &mut Settings, menu: &mut SettingsMenu, player: usize, action: Action, key: VirtualKeyCode| {
+        let mut inputs = FrameInputs::new(Rect::new(0.0, 0.0, 1.0, 1.0), 1);
+        inputs.curr_keys.insert(key);
+        menu.player = player;
+        menu.rebinding = Some(action);
+        menu.update(s, &inputs);
+        assert!(menu.rebinding.is_none());
+    };
+    press(&mut s, &mut menu, 0, Action::Pause, VirtualKeyCode::Escape);
+    assert!(s.bindings[0].contains(Action::Pause, Binding::Key(VirtualKeyCode::Escape)));
+    press(&mut s, &mut menu, 1, Action::Pause, VirtualKeyCode::Escape);
+    assert!(!s.bindings[1].contains(Action::Pause, Binding::Key(VirtualKeyCode::Escape)));
+    // and tab stays with the high scores
+    press(&mut s, &mut menu, 0, Action::Fire, VirtualKeyCode::Tab);
+    assert!(!s.bindings[0].contains(Action::Fire, Binding::Key(VirtualKeyCode::Tab)));
+    assert_eq!(menu.message, "Tab is for high scores");
+}
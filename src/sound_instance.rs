@@ -14,16 +14,28 @@ pub const ENEMY_DIE: u32 = 3;
 pub const ENEMY_SPAWN: u32 = 4;
 pub const LASER_POP: u32 = 5;
 pub const POWERUP: u32 = 6;
+pub const SPREAD: u32 = 7;
+pub const MISSILE: u32 = 8;
+pub const CHARGE: u32 = 9;
+pub const BEAM: u32 = 10;
+pub const BOMB: u32 = 11;
+pub const UPGRADE: u32 = 12;
+pub const SHIELD: u32 = 13;
+pub const SPEED: u32 = 14;
+pub const COOLING: u32 = 15;
+pub const MULTIPLIER: u32 = 16;
+pub const EXTRA_LIFE: u32 = 17;
+pub const PIERCE: u32 = 18;
 
 const PHASE_UNIT: f32 = 2.0 * PI / 44100.0; // phase unit
-                                //   laser      eshoot  pdie    edie    espawn  lpop  pwrup
-const duration: [u64; 9] =          [u64::MAX,  2500,   15000,  10000,  2500,  5000,  30000, 100, 100];
-const freq_base: [f32; 9] =         [110.0,     150.0,  110.0,  880.0,  200.0,  666.0,  110.0, 100.0, 100.0];
-const freq_mult_end: [f32; 9] =     [1.0,       0.5,    1.0,    1.0,    0.5,    1.0,    0.0, 1.0, 1.0];
-const freq_mult_range: [f32; 9] =   [0.0,       2.0,    0.0,    0.0,    0.0,    0.0,    0.0, 0.0, 0.0];
-const amp: [f32; 9] =               [0.1,       0.05,    0.6,    0.03,   0.0,    0.1,    0.1, 0.1, 0.1, ];
-const amp_start: [f32; 9] =         [1.0,       1.0,    1.0,    1.0,    0.0,    1.0,    1.0, 1.0, 1.0, ];
-const amp_end: [f32; 9] =           [1.0,       0.8,    0.0,    0.0,    1.0,    0.0,    0.0, 1.0, 1.0, ];
+                                //   laser     eshoot    pdie      edie      espawn    lpop      pwrup     spread    missile   charge    beam      bomb      upgrade   shield    speed     cooling   mult      life      pierce
+const duration: [u64; 19] =         [u64::MAX, 2500,     15000,    10000,    2500,     5000,     30000,    2000,     6000,     u64::MAX, 20000,    40000,    15000,    20000,    12000,    20000,    15000,    40000,    15000];
+const freq_base: [f32; 19] =        [110.0,    150.0,    110.0,    880.0,    200.0,    666.0,    110.0,    440.0,    220.0,    80.0,     55.0,     60.0,     330.0,    150.0,    300.0,    880.0,    440.0,    523.0,    1200.0];
+const freq_mult_end: [f32; 19] =    [1.0,      0.5,      1.0,      1.0,      0.5,      1.0,      0.0,      0.5,      2.0,      1.0,      1.0,      0.2,      2.0,      1.5,      3.0,      0.25,     1.5,      2.0,      0.5];
+const freq_mult_range: [f32; 19] =  [0.0,      2.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.5,      0.2,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0];
+const amp: [f32; 19] =              [0.1,      0.05,     0.6,      0.03,     0.0,      0.1,      0.1,      0.04,     0.05,     0.06,     0.15,     0.5,      0.08,     0.08,     0.06,     0.05,     0.07,     0.08,     0.05];
+const amp_start: [f32; 19] =        [1.0,      1.0,      1.0,      1.0,      0.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0,      1.0];
+const amp_end: [f32; 19] =          [1.0,      0.8,      0.0,      0.0,      1.0,      0.0,      0.0,      0.0,      0.3,      1.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0,      0.0];
 
 pub struct SoundInstance {
     pub birth: u64,
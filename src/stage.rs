@@ -0,0 +1,299 @@
+use crate::kmath::*;
+use crate::archetype::*;
+
+// Authored stages, as opposed to the endless mode where every enemy type just
+// spawns on its own schedule. A stage is a timeline of spawns with some
+// checkpoints along the way and a condition for when its beaten.
+//
+// stage <name>
+// clear all | time <t> | boss         default is all: everything spawned and dead
+// checkpoint <t>                      dying after this restarts from here
+// at <t> <enemy> [formation] [entry] [path]
+//   formation: line <n> <spacing> | column <n> <spacing> | vee <n> <spacing>
+//   entry:     y <y> | random         otherwise whatever the enemy says
+//   path:      path <x1> <y1> <x2> <y2> <x3> <y3> <duration>
+//              bezier from where it comes in, points are relative to that.
+//              after <duration> seconds it goes back to moving like normal
+// at <t> boss <enemy> [entry]         comes in, stops at the right of the screen and stays
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Formation {
+    Single,
+    Line { n: u32, spacing: f32 },
+    Column { n: u32, spacing: f32 },
+    Vee { n: u32, spacing: f32 },
+}
+
+impl Formation {
+    // where each one goes relative to the entry point
+    pub fn offsets(&self) -> Vec<Vec2> {
+        match *self {
+            Formation::Single => vec![v2(0.0, 0.0)],
+            Formation::Line { n, spacing } => (0..n).map(|i| v2(i as f32 * spacing, 0.0)).collect(),
+            Formation::Column { n, spacing } => {
+                let mid = (n as f32 - 1.0) / 2.0;
+                (0..n).map(|i| v2(0.0, (i as f32 - mid) * spacing)).collect()
+            },
+            Formation::Vee { n, spacing } => {
+                let mid = (n as f32 - 1.0) / 2.0;
+                (0..n).map(|i| v2((i as f32 - mid).abs() * spacing, (i as f32 - mid) * spacing)).collect()
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    pub start: Vec2,
+    pub c1: Vec2,
+    pub c2: Vec2,
+    pub end: Vec2,
+    pub duration: f32,
+}
+
+impl Path {
+    // <x1> <y1> <x2> <y2> <x3> <y3> <duration>, starting from 0,0
+    pub fn parse(words: &mut std::slice::Iter<&str>) -> Result<Path, anyhow::Error> {
+        let mut v = [0.0; 7];
+        for x in v.iter_mut() {
+            *x = number(words.next())?;
+        }
+        Ok(Path {
+            start: v2(0.0, 0.0),
+            c1: v2(v[0], v[1]),
+            c2: v2(v[2], v[3]),
+            end: v2(v[4], v[5]),
+            duration: v[6],
+        })
+    }
+
+    pub fn at(&self, s: f32) -> Vec2 {
+        cubic_bezier(self.start, self.c1, self.c2, self.end, s)
+    }
+
+    // turned around start
+    pub fn rotated(&self, radians: f32) -> Path {
+        Path {
+            start: self.start,
+            c1: self.start + (self.c1 - self.start).rotate(radians),
+            c2: self.start + (self.c2 - self.start).rotate(radians),
+            end: self.start + (self.end - self.start).rotate(radians),
+            duration: self.duration,
+        }
+    }
+
+    pub fn moved(&self, by: Vec2) -> Path {
+        Path {
+            start: self.start + by,
+            c1: self.c1 + by,
+            c2: self.c2 + by,
+            end: self.end + by,
+            duration: self.duration,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnEvent {
+    pub t: f32,
+    pub archetype: String,
+    pub formation: Formation,
+    pub entry: Option<Entry>,
+    pub path: Option<Path>,
+    pub boss: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Clear {
+    All,
+    Time(f32),
+    Boss,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub events: Vec<SpawnEvent>,
+    pub checkpoints: Vec<f32>,
+    pub clear: Clear,
+    // the text it came from, so replays can carry it around
+    pub source: String,
+}
+
+impl Stage {
+    pub fn parse(s: &str) -> Result<Stage, anyhow::Error> {
+        let mut stage = Stage {
+            name: "stage".to_owned(),
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+            clear: Clear::All,
+            source: s.to_owned(),
+        };
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+            stage.parse_line(&words).map_err(|e| anyhow::Error::msg(format!("line {}: {}", i + 1, e)))?;
+        }
+        if stage.events.is_empty() {
+            return Err(anyhow::Error::msg("nothing happens in this stage"));
+        }
+        if stage.clear == Clear::Boss && !stage.events.iter().any(|e| e.boss) {
+            return Err(anyhow::Error::msg("clear boss but theres no boss"));
+        }
+        Ok(stage)
+    }
+
+    fn parse_line(&mut self, words: &[&str]) -> Result<(), anyhow::Error> {
+        let args = &words[1..];
+        match words[0] {
+            "stage" => self.name = args.join(" "),
+            "clear" => self.clear = match args.first() {
+                Some(&"all") => Clear::All,
+                Some(&"time") => Clear::Time(number(args.get(1))?),
+                Some(&"boss") => Clear::Boss,
+                _ => return Err(anyhow::Error::msg("clear is all, time or boss")),
+            },
+            "checkpoint" => {
+                let t = number(args.first())?;
+                if self.checkpoints.last().map(|c| *c > t).unwrap_or(false) {
+                    return Err(anyhow::Error::msg("checkpoints have to go in order"));
+                }
+                self.checkpoints.push(t);
+            },
+            "at" => {
+                let event = parse_event(args)?;
+                if self.events.last().map(|e| e.t > event.t).unwrap_or(false) {
+                    return Err(anyhow::Error::msg("times have to go in order"));
+                }
+                self.events.push(event);
+            },
+            w => return Err(anyhow::Error::msg(format!("dont know '{}'", w))),
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Stage, anyhow::Error> {
+        let stage = Stage::parse(&std::fs::read_to_string(path)?)?;
+        stage.check(&load_archetypes())?;
+        Ok(stage)
+    }
+
+    // every enemy it mentions has to exist
+    pub fn check(&self, archetypes: &[Archetype]) -> Result<(), anyhow::Error> {
+        for e in self.events.iter() {
+            if !archetypes.iter().any(|a| a.name == e.archetype) {
+                return Err(anyhow::Error::msg(format!("no enemy called '{}'", e.archetype)));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_event(args: &[&str]) -> Result<SpawnEvent, anyhow::Error> {
+    let t = number(args.first())?;
+    let mut words = args[1..].iter();
+    let mut boss = false;
+    let mut archetype = words.next().ok_or_else(|| anyhow::Error::msg("at needs an enemy"))?;
+    if *archetype == "boss" {
+        boss = true;
+        archetype = words.next().ok_or_else(|| anyhow::Error::msg("boss needs an enemy"))?;
+    }
+    let mut event = SpawnEvent {
+        t,
+        archetype: archetype.to_string(),
+        formation: Formation::Single,
+        entry: None,
+        path: None,
+        boss,
+    };
+    while let Some(w) = words.next() {
+        match *w {
+            "line" | "column" | "vee" => {
+                let n = number(words.next())? as u32;
+                let spacing = number(words.next())?;
+                event.formation = match *w {
+                    "line" => Formation::Line { n, spacing },
+                    "column" => Formation::Column { n, spacing },
+                    _ => Formation::Vee { n, spacing },
+                };
+            },
+            "y" => event.entry = Some(Entry::Y(number(words.next())?)),
+            "random" => event.entry = Some(Entry::Random),
+            "path" => event.path = Some(Path::parse(&mut words)?),
+            w => return Err(anyhow::Error::msg(format!("dont know '{}'", w))),
+        }
+    }
+    if boss && event.formation != Formation::Single {
+        return Err(anyhow::Error::msg("only one boss at a time"));
+    }
+    Ok(event)
+}
+
+// stages and bullet patterns both read numbers and paths out of a line of words
+pub fn number(w: Option<&&str>) -> Result<f32, anyhow::Error> {
+    let w = w.ok_or_else(|| anyhow::Error::msg("ran out of numbers"))?;
+    w.parse::<f32>().map_err(|_| anyhow::Error::msg(format!("'{}' isnt a number", w)))
+}
+
+// how far into a stage we are
+#[derive(Clone, Debug, PartialEq)]
+pub struct StageRun {
+    pub stage: Stage,
+    pub next_event: usize,
+    pub checkpoints_reached: usize,
+    pub checkpoint_scores: Vec<i32>,
+    pub t_checkpoint: f32,
+    pub boss_killed: bool,
+    pub t_cleared: Option<f32>,
+}
+
+impl StageRun {
+    pub fn new(stage: Stage) -> StageRun {
+        StageRun {
+            stage,
+            next_event: 0,
+            checkpoints_reached: 0,
+            checkpoint_scores: Vec::new(),
+            t_checkpoint: -10.0,
+            boss_killed: false,
+            t_cleared: None,
+        }
+    }
+
+    pub fn checkpoint_t(&self) -> f32 {
+        if self.checkpoints_reached == 0 {
+            0.0
+        } else {
+            self.stage.checkpoints[self.checkpoints_reached - 1]
+        }
+    }
+
+    // a fresh go at the same stage from the last checkpoint this one got to
+    pub fn restarted_at_checkpoint(&self) -> StageRun {
+        let t = self.checkpoint_t();
+        let mut run = StageRun::new(self.stage.clone());
+        run.next_event = self.stage.events.iter().position(|e| e.t >= t).unwrap_or(self.stage.events.len());
+        run.checkpoints_reached = self.checkpoints_reached;
+        run.checkpoint_scores = self.checkpoint_scores.clone();
+        run
+    }
+}
+
+#[test]
+fn test_parse_stage() {
+    let stage = Stage::parse("stage test\nclear boss\nat 1 saucer line 3 0.1 y 0.2\ncheckpoint 5\nat 6 dart vee 5 0.05 path 0 0.2 -0.3 0.2 -0.3 0 2\nat 10 boss orb\n").unwrap();
+    assert_eq!(stage.name, "test");
+    assert_eq!(stage.events.len(), 3);
+    assert_eq!(stage.events[0].formation.offsets()[2], v2(0.2, 0.0));
+    assert_eq!(stage.events[1].formation.offsets()[0], v2(0.1, -0.1));
+    assert_eq!(stage.events[1].path.as_ref().unwrap().duration, 2.0);
+    assert!(stage.events[2].boss);
+    assert!(stage.check(&builtin_archetypes()).is_ok());
+
+    assert!(Stage::parse("clear boss\nat 1 saucer\n").is_err());
+    assert!(Stage::parse("at 2 saucer\nat 1 saucer\n").unwrap_err().to_string().starts_with("line 2"));
+    assert!(Stage::parse("at 1 nope\n").unwrap().check(&builtin_archetypes()).is_err());
+}
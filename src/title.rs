@@ -0,0 +1,96 @@
+use crate::game::MAX_PLAYERS;
+use crate::kinput::*;
+use crate::kmath::*;
+use crate::scene::*;
+use crate::widgets::{Menu, Nav};
+
+// What you get before the game. Play and co-op swap it out for a game set up
+// however the command line said, left and right on co-op picks how many players.
+// Its all driven through Nav so anything that can fill one in can work the menu.
+
+const STARS: u32 = 80;
+
+pub struct TitleScreen {
+    menu: Menu,
+    args: Vec<String>,
+    players: u32,
+    change: Option<SceneChange>,
+    t: f32,
+}
+
+impl TitleScreen {
+    pub fn new(args: &[String]) -> TitleScreen {
+        let players = crate::arg_value(args, "--players").and_then(|p| p.parse::<u32>().ok()).unwrap_or(2);
+        TitleScreen {
+            menu: Menu::new(),
+            args: args.to_vec(),
+            players: players.clamp(2, MAX_PLAYERS as u32),
+            change: None,
+            t: 0.0,
+        }
+    }
+
+    fn rows(&self) -> Vec<String> {
+        vec![
+            "play".to_owned(),
+            format!("co-op: < {} players >", self.players),
+            "high scores".to_owned(),
+            "options".to_owned(),
+            "quit".to_owned(),
+        ]
+    }
+}
+
+// straight out of the registry theres no command line to go on
+impl Default for TitleScreen {
+    fn default() -> TitleScreen {
+        TitleScreen::new(&[])
+    }
+}
+
+impl Demo for TitleScreen {
+    fn frame(&mut self, inputs: &FrameInputs, outputs: &mut FrameOutputs) {
+        self.t += inputs.dt;
+        let nav = Nav::from_inputs(inputs);
+        let rows = self.rows();
+        self.menu.update(rows.len(), nav, inputs);
+        if self.menu.selected == 1 {
+            if nav.left {
+                self.players = (self.players - 1).max(2);
+            }
+            if nav.right {
+                self.players = (self.players + 1).min(MAX_PLAYERS as u32);
+            }
+        }
+        if let Some(i) = self.menu.chosen(nav, inputs) {
+            self.change = Some(match i {
+                0 => SceneChange::Replace(crate::game_scene(&self.args, Some(1)), Transition::Fade),
+                1 => SceneChange::Replace(crate::game_scene(&self.args, Some(self.players)), Transition::Fade),
+                2 => SceneChange::HighScores,
+                3 => SceneChange::Options,
+                _ => SceneChange::Quit,
+            });
+        }
+
+        let r = inputs.screen_rect;
+        outputs.canvas.put_rect(r, 0.5, Vec4::new(0.02, 0.02, 0.08, 1.0));
+        // stars going past, the far ones slower and dimmer
+        for i in 0..STARS {
+            let depth = krand(khash(i * 3 + 1));
+            let speed = 0.05 + 0.3 * depth;
+            let x = (krand(khash(i * 3)) * (r.w + 0.1) - speed * self.t).rem_euclid(r.w + 0.1) - 0.05;
+            let y = krand(khash(i * 3 + 2)) * r.h;
+            let s = 0.002 + 0.004 * depth;
+            outputs.canvas.put_rect(v2(x, y).rect_centered(s, s), 0.6, Vec4::grey(0.3 + 0.7 * depth));
+        }
+        let bob = 0.01 * (self.t * 2.0).sin();
+        outputs.glyphs.push_center_str("ssss", r.w/2.0, r.h * 0.2 + bob, 0.12, 0.12, 2.1, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        outputs.glyphs.push_center_str("side scrolling space shooter", r.w/2.0, r.h * 0.34, 0.025, 0.025, 2.1, Vec4::grey(0.7));
+        self.menu.draw(&rows, r.w/2.0, r.h * 0.5, 0.04, 2.1, outputs);
+        outputs.glyphs.push_center_str("arrows or mouse to pick - return to go", r.w/2.0, r.h * 0.92, 0.02, 0.02, 2.1, Vec4::grey(0.5));
+    }
+
+    fn scene_change(&mut self) -> Option<SceneChange> {
+        self.change.take()
+    }
+}
@@ -0,0 +1,340 @@
+use crate::kmath::*;
+use crate::audio::*;
+use crate::sound_instance::*;
+
+// The players guns. Everyone has all of them and flips through them with the
+// switch key, and each one runs out its own way: the laser and the spread shot
+// get hot and have to cool off, missiles and bombs are ammo that comes back
+// slowly and the beam charges up while fire is held and goes off when its let
+// go. Picking up a powerup levels up whichever one is out.
+
+pub const MAX_LEVEL: u32 = 3;
+
+pub const LASER_H: f32 = 0.005;
+const BEAM_H: f32 = 0.05;
+const MIN_CHARGE: f32 = 0.2;
+
+const SPREAD_INTERVAL: f32 = 0.15;
+const SPREAD_HEAT: f32 = 0.1;       // per shot, it cools at SPREAD_COOL a second
+const SPREAD_COOL: f32 = 0.4;
+const SPREAD_ANGLE: f32 = 0.5;
+
+const MISSILE_INTERVAL: f32 = 0.3;
+const MISSILE_RELOAD: f32 = 1.0;    // seconds per missile
+const MISSILE_TURN: f32 = 4.0;      // radians a second
+
+const BOMB_RELOAD: f32 = 20.0;
+
+pub const SHOT_SPEED: f32 = 1.2;
+pub const MISSILE_SPEED: f32 = 0.6;
+pub const SHOT_R: f32 = 0.008;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeaponKind {
+    Laser,
+    Spread,
+    Missiles,
+    Beam,
+    Bombs,
+}
+
+pub const WEAPON_KINDS: [WeaponKind; 5] = [WeaponKind::Laser, WeaponKind::Spread, WeaponKind::Missiles, WeaponKind::Beam, WeaponKind::Bombs];
+
+impl WeaponKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            WeaponKind::Laser => "laser",
+            WeaponKind::Spread => "spread",
+            WeaponKind::Missiles => "missiles",
+            WeaponKind::Beam => "beam",
+            WeaponKind::Bombs => "bombs",
+        }
+    }
+}
+
+// something going right from the player, hurting whatever its touching every step until `until`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Beam {
+    pub kind: WeaponKind,
+    pub h: f32,
+    pub dps: f32,
+    pub until: f32,
+}
+
+impl Beam {
+    pub fn rect(&self, from: Vec2) -> Rect {
+        Rect::new(from.x, from.y - self.h/2.0, 100.0, self.h)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shot {
+    pub pos: Vec2,
+    pub v: Vec2,
+    pub damage: f32,
+    pub homing: bool,
+    pub owner: usize,
+}
+
+impl Shot {
+    // missiles turn towards target as fast as they can
+    pub fn update(&mut self, dt: f32, target: Option<Vec2>) {
+        if let (true, Some(target)) = (self.homing, target) {
+            let want = target - self.pos;
+            let angle = (self.v.x * want.y - self.v.y * want.x).atan2(self.v.x * want.x + self.v.y * want.y);
+            self.v = self.v.rotate(angle.clamp(-MISSILE_TURN * dt, MISSILE_TURN * dt));
+        }
+        self.pos = self.pos + dt * self.v;
+    }
+}
+
+// what came out of the guns this step
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Firing {
+    pub shots: Vec<Shot>,
+    // damage to everything on screen
+    pub bomb: Option<f32>,
+    pub audio_events: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weapon {
+    pub kind: WeaponKind,
+    pub level: u32,
+    // heat for the laser and spread, charge for the beam, ammo for missiles and bombs
+    pub meter: f32,
+    // got too hot, no shooting until its all the way cool again
+    pub cooling: bool,
+    pub t_last_fire: f32,
+}
+
+impl Weapon {
+    pub fn new(kind: WeaponKind) -> Weapon {
+        let mut w = Weapon { kind, level: 1, meter: 0.0, cooling: false, t_last_fire: -10.0 };
+        w.meter = w.ammo_max();
+        w
+    }
+
+    // 0 for the ones that arent ammo
+    pub fn ammo_max(&self) -> f32 {
+        match self.kind {
+            WeaponKind::Missiles => 2.0 + 2.0 * self.level as f32,
+            WeaponKind::Bombs => self.level as f32,
+            _ => 0.0,
+        }
+    }
+
+    // how full the hud gauge is
+    pub fn gauge(&self) -> f32 {
+        match self.kind {
+            WeaponKind::Missiles | WeaponKind::Bombs => self.meter / self.ammo_max(),
+            _ => self.meter,
+        }
+    }
+
+    fn cool(&mut self, amount: f32) {
+        self.meter -= amount;
+        if self.meter <= 0.0 {
+            self.meter = 0.0;
+            self.cooling = false;
+        }
+    }
+
+    fn heat(&mut self, amount: f32, firing: &mut Firing) {
+        self.meter += amount;
+        if self.meter > 1.0 {
+            self.meter = 1.0;
+            self.cooling = true;
+            firing.audio_events.push(SOUND_PLAY | LASER_POP);
+        }
+    }
+
+    // held is only true if this is the one thats out, the others just cool down and reload
+    #[allow(clippy::too_many_arguments)]
+    fn update(&mut self, held: bool, pressed: bool, released: bool, t: f32, dt: f32, pos: Vec2, owner: usize, firing: &mut Firing) -> Option<Beam> {
+        let level = self.level as f32;
+        match self.kind {
+            WeaponKind::Laser => {
+                if held && !self.cooling {
+                    self.heat(dt, firing);
+                    firing.audio_events.push(SOUND_PLAY | SOUND_UNIQUE | LASER);
+                    return Some(Beam { kind: self.kind, h: LASER_H * (0.5 + 0.5 * level), dps: 0.5 + 0.5 * level, until: t });
+                }
+                self.cool(dt);
+            },
+            WeaponKind::Spread => {
+                self.cool(SPREAD_COOL * dt);
+                if held && !self.cooling && t - self.t_last_fire >= SPREAD_INTERVAL {
+                    let n = 1 + 2 * self.level;
+                    for j in 0..n {
+                        let theta = SPREAD_ANGLE * (j as f32 / (n - 1) as f32 - 0.5);
+                        firing.shots.push(Shot { pos, v: SHOT_SPEED * v2(theta.cos(), theta.sin()), damage: 0.15, homing: false, owner });
+                    }
+                    self.t_last_fire = t;
+                    self.heat(SPREAD_HEAT, firing);
+                    firing.audio_events.push(SOUND_PLAY | SPREAD);
+                }
+            },
+            WeaponKind::Missiles => {
+                self.meter = (self.meter + dt / MISSILE_RELOAD).min(self.ammo_max());
+                if held && self.meter >= 1.0 && t - self.t_last_fire >= MISSILE_INTERVAL {
+                    self.meter -= 1.0;
+                    self.t_last_fire = t;
+                    // two for one at max level
+                    let thetas: &[f32] = if self.level >= MAX_LEVEL { &[-0.6, 0.6] } else { &[0.0] };
+                    for theta in thetas {
+                        firing.shots.push(Shot { pos, v: MISSILE_SPEED * v2(theta.cos(), theta.sin()), damage: 0.3 + 0.1 * level, homing: true, owner });
+                    }
+                    firing.audio_events.push(SOUND_PLAY | MISSILE);
+                }
+            },
+            WeaponKind::Beam => {
+                if held {
+                    self.meter = (self.meter + dt * (0.5 + 0.25 * level)).min(1.0);
+                    firing.audio_events.push(SOUND_PLAY | SOUND_UNIQUE | CHARGE);
+                } else if released && self.meter >= MIN_CHARGE {
+                    let charge = self.meter;
+                    self.meter = 0.0;
+                    self.t_last_fire = t;
+                    firing.audio_events.push(SOUND_PLAY | BEAM);
+                    return Some(Beam { kind: self.kind, h: BEAM_H * charge, dps: 3.0 + level, until: t + charge });
+                } else {
+                    self.meter = 0.0;
+                }
+            },
+            WeaponKind::Bombs => {
+                self.meter = (self.meter + dt / BOMB_RELOAD).min(self.ammo_max());
+                if pressed && self.meter >= 1.0 {
+                    self.meter -= 1.0;
+                    self.t_last_fire = t;
+                    firing.bomb = Some(1.5 * level);
+                    firing.audio_events.push(SOUND_PLAY | BOMB);
+                }
+            },
+        }
+        None
+    }
+}
+
+// one players set of weapons
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arsenal {
+    pub weapons: Vec<Weapon>,
+    pub current: usize,
+    pub beam: Option<Beam>,
+    // nothing heats up
+    pub cooled: bool,
+    fire_held: bool,
+    switch_held: bool,
+}
+
+impl Default for Arsenal {
+    fn default() -> Self {
+        Arsenal {
+            weapons: WEAPON_KINDS.iter().map(|k| Weapon::new(*k)).collect(),
+            current: 0,
+            beam: None,
+            cooled: false,
+            fire_held: false,
+            switch_held: false,
+        }
+    }
+}
+
+impl Arsenal {
+    pub fn weapon(&self) -> &Weapon {
+        &self.weapons[self.current]
+    }
+
+    // levels up whichever ones out, returns false if its already maxed
+    pub fn upgrade(&mut self) -> bool {
+        let w = &mut self.weapons[self.current];
+        if w.level >= MAX_LEVEL {
+            return false;
+        }
+        w.level += 1;
+        w.meter = w.meter.max(w.ammo_max());
+        true
+    }
+
+    // the beam thats on at t, if there is one
+    pub fn beam_at(&self, t: f32) -> Option<Beam> {
+        self.beam.filter(|b| b.until >= t)
+    }
+
+    // fire and switch are held or not, edges get worked out in here
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(&mut self, fire: bool, switch: bool, in_play: bool, t: f32, dt: f32, pos: Vec2, owner: usize) -> Firing {
+        let mut firing = Firing::default();
+        if switch && !self.switch_held && in_play {
+            self.current = (self.current + 1) % self.weapons.len();
+        }
+        self.switch_held = switch;
+        let pressed = fire && !self.fire_held && in_play;
+        let released = !fire && self.fire_held && in_play;
+        self.fire_held = fire;
+
+        if !in_play || self.beam_at(t).is_none() {
+            self.beam = None;
+        }
+        for (i, w) in self.weapons.iter_mut().enumerate() {
+            let out = i == self.current;
+            if let Some(beam) = w.update(out && fire && in_play, out && pressed, out && released, t, dt, pos, owner, &mut firing) {
+                self.beam = Some(beam);
+            }
+        }
+        if self.cooled {
+            for w in self.weapons.iter_mut().filter(|w| matches!(w.kind, WeaponKind::Laser | WeaponKind::Spread)) {
+                w.meter = 0.0;
+                w.cooling = false;
+            }
+        }
+        firing
+    }
+}
+
+#[test]
+fn test_weapons() {
+    let dt = 1.0 / 120.0;
+    let step = |a: &mut Arsenal, i: usize, fire: bool, switch: bool| a.update(fire, switch, true, i as f32 * dt, dt, v2(0.1, 0.5), 0);
+
+    // laser overheats after a second and has to cool all the way
+    let mut a = Arsenal::default();
+    for i in 0..130 {
+        step(&mut a, i, true, false);
+    }
+    assert!(a.weapon().cooling && a.beam_at(130.0 * dt).is_none());
+
+    // spread shot fires 3 then 5 once its upgraded
+    step(&mut a, 200, false, true);
+    assert_eq!(a.weapon().kind, WeaponKind::Spread);
+    assert_eq!(step(&mut a, 201, true, false).shots.len(), 3);
+    assert!(a.upgrade());
+    assert_eq!(step(&mut a, 300, true, false).shots.len(), 5);
+
+    // missiles run out
+    step(&mut a, 400, false, true);
+    let mut fired = 0;
+    for i in 401..1000 {
+        fired += step(&mut a, i, true, false).shots.len();
+    }
+    assert!(fired > 4 && fired < 20);
+
+    // beam needs charging and goes off on release
+    step(&mut a, 1000, false, true);
+    step(&mut a, 1001, true, false);
+    step(&mut a, 1002, false, false);
+    assert!(a.beam_at(1002.0 * dt).is_none());
+    for i in 1003..1250 {
+        step(&mut a, i, true, false);
+    }
+    step(&mut a, 1250, false, false);
+    let beam = a.beam_at(1300.0 * dt).unwrap();
+    assert_eq!(beam.kind, WeaponKind::Beam);
+
+    // one bomb per press, then it has to reload
+    step(&mut a, 1400, false, true);
+    assert!(step(&mut a, 1401, true, false).bomb.is_some());
+    step(&mut a, 1402, false, false);
+    assert!(step(&mut a, 1403, true, false).bomb.is_none());
+}
@@ -1,3 +1,6 @@
+use glutin::event::VirtualKeyCode;
+use gilrs::Button;
+
 use crate::kmath::*;
 use crate::scene::*;
 use crate::kinput::*;
@@ -121,3 +124,94 @@ impl FloatSlider {
         return any_change;
     }
 }
+
+// What menus listen for. Keys go in here and anything else that can drive a menu
+// should too, so every menu works the same whatever its being driven with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Nav {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub confirm: bool,
+    pub back: bool,
+}
+
+impl Nav {
+    // arrows or wasd and return, or any pads dpad or stick and a, b to go back
+    pub fn from_inputs(inputs: &FrameInputs) -> Nav {
+        let any = |keys: &[VirtualKeyCode]| keys.iter().any(|k| inputs.key_press_or_repeat(*k));
+        let pad = |button: Button, dir: Vec2| inputs.pads.iter().any(|p| p.rising(button) || p.flicked(dir));
+        Nav {
+            up: any(&[VirtualKeyCode::Up, VirtualKeyCode::W]) || pad(Button::DPadUp, Vec2::new(0.0, -1.0)),
+            down: any(&[VirtualKeyCode::Down, VirtualKeyCode::S]) || pad(Button::DPadDown, Vec2::new(0.0, 1.0)),
+            left: any(&[VirtualKeyCode::Left, VirtualKeyCode::A]) || pad(Button::DPadLeft, Vec2::new(-1.0, 0.0)),
+            right: any(&[VirtualKeyCode::Right, VirtualKeyCode::D]) || pad(Button::DPadRight, Vec2::new(1.0, 0.0)),
+            confirm: inputs.key_rising(VirtualKeyCode::Return) || inputs.key_rising(VirtualKeyCode::Space) || inputs.any_pad_rising(Button::South),
+            back: inputs.key_rising(VirtualKeyCode::Escape) || inputs.any_pad_rising(Button::East),
+        }
+    }
+}
+
+// A column of rows with one of them focused. Up and down move the focus, so does
+// pointing at one with the mouse, and confirm or clicking picks it. Whoever owns
+// it says what the rows are each frame.
+pub struct Menu {
+    pub selected: usize,
+    // where each row went the last time it was drawn, for the mouse
+    rects: Vec<Rect>,
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu { selected: 0, rects: Vec::new() }
+    }
+
+    fn under_mouse(&self, inputs: &FrameInputs) -> Option<usize> {
+        self.rects.iter().position(|r| r.contains(inputs.mouse_pos))
+    }
+
+    // n is how many rows there are right now
+    pub fn update(&mut self, n: usize, nav: Nav, inputs: &FrameInputs) {
+        if n == 0 {
+            return;
+        }
+        if nav.up {
+            self.selected = (self.selected + n - 1) % n;
+        }
+        if nav.down {
+            self.selected = (self.selected + 1) % n;
+        }
+        // only when it moves, otherwise a mouse sat over a row would fight the keys
+        if inputs.mouse_delta != Vec2::new(0.0, 0.0) || inputs.lmb == KeyStatus::JustPressed {
+            if let Some(i) = self.under_mouse(inputs) {
+                self.selected = i;
+            }
+        }
+        self.selected = self.selected.min(n - 1);
+    }
+
+    // the row that got picked this frame
+    pub fn chosen(&self, nav: Nav, inputs: &FrameInputs) -> Option<usize> {
+        if nav.confirm || (inputs.lmb == KeyStatus::JustPressed && self.under_mouse(inputs) == Some(self.selected)) {
+            Some(self.selected)
+        } else {
+            None
+        }
+    }
+
+    // centered on x going down from y, h is the text height
+    pub fn draw(&mut self, rows: &[String], x: f32, y: f32, h: f32, depth: f32, outputs: &mut FrameOutputs) {
+        self.rects.clear();
+        for (i, row) in rows.iter().enumerate() {
+            let y = y + i as f32 * h * 1.6;
+            let w = (row.len() + 4) as f32 * h;
+            self.rects.push(Rect::new(x - w/2.0, y - 0.3 * h, w, 1.6 * h));
+            if i == self.selected {
+                outputs.glyphs.push_center_str(&format!("> {} <", row), x, y, h, h, depth, Vec4::new(1.0, 1.0, 0.0, 1.0));
+            } else {
+                outputs.glyphs.push_center_str(row, x, y, h, h, depth, Vec4::grey(0.8));
+            }
+        }
+    }
+}